@@ -1,6 +1,15 @@
-use crate::pci::{self, PciDevice};
-use crate::usb::{self, UsbDevice};
-use anyhow::Result;
+use crate::audio::{self, AudioPowerSave};
+use crate::cpu::{self, CpuPolicy};
+use crate::disk::{self, Disk};
+use crate::pci::{self, AspmPolicy, LinkState, PciCapabilities, PciDevice, PciMatcher};
+use crate::platform::{self, PlatformProfile};
+use crate::power_supply::{self, Battery, BatteryReading, Measurement, PowerMeter, PowerSample};
+use crate::profile::{self, AutoSwitch, Profile};
+use crate::sata::{self, SataHost};
+use crate::sleep::{self, MemSleep};
+use crate::usb::{self, UsbAuthorizedDefault, UsbDevice, UsbMatcher};
+use crate::wakeup::{self, AcpiWakeupDevice, WakeupSource};
+use anyhow::{anyhow, bail, Result};
 use gio::prelude::*;
 use gio::subclass::prelude::ApplicationImpl;
 use glib::subclass::{self, prelude::*};
@@ -11,9 +20,19 @@ use gtk::prelude::*;
 use gtk::subclass::application::GtkApplicationImpl;
 use log::*;
 use std::cell::RefCell;
+use std::path::Path;
 use std::rc::Rc;
 use std::time::Duration;
 
+/// Ids of the driver filter entries that aren't driver names.
+const ALL_DRIVERS: &str = "*all";
+const NO_DRIVER: &str = "*none";
+/// Id of the automatic switching entry that doesn't apply any profile.
+const NO_PROFILE: &str = "*none";
+/// How long the devices are given to settle after applying the changes of a
+/// measurement, in seconds.
+const MEASURE_SETTLE_TIME: u32 = 5;
+
 macro_rules! get_widget {
     ($name:ident, $wtype:ty, $builder:expr) => {
         let $name: $wtype = $builder.get_object(stringify!($name)).expect(&format!(
@@ -57,13 +76,49 @@ macro_rules! activate {
 pub enum Action {
     ApplyChanges,
     Refresh,
-    ResetChanged,
-    SetUsbAutoSuspend(u32, bool),
-    SetUsbAutoSuspendDelay(gtk::ComboBoxText, u32, String),
-    SetUsbAllowWakeup(u32, bool),
+    SetUsbAutoSuspend(String, bool),
+    SetUsbAutoSuspendDelay(gtk::ComboBoxText, String, String),
+    SetUsbAllowWakeup(String, bool),
+    SetUsbAuthorized(String, bool),
+    SetUsbHardwareLpm(String, bool),
+    SetUsbPersist(String, bool),
+    ConfirmUsbPersistOff(gtk::Switch, String),
+    SetUsbAuthorizedDefault(String, String),
+    ConfirmUsbDeauthorize(gtk::Switch, String),
+    ShowUsbDetails(gtk::ListBoxRow, String),
+    ShowPciDetails(gtk::ListBoxRow, String),
     SetPciAutoSuspend(String, bool),
     SetPciAutoSuspendDelay(gtk::ComboBoxText, String, String),
+    SetPciD3coldAllowed(String, bool),
+    SetPciAllowWakeup(String, bool),
+    SetPciLinkState(String, LinkState, bool),
+    SetAspmPolicy(String),
+    SetPlatformProfile(String),
+    SetMemSleep(String),
+    PersistMemSleep,
+    SetBatteryThresholds(String, Option<gtk::SpinButton>, Option<gtk::SpinButton>),
+    SetSataPolicy(String, String),
+    SetDiskAutoSuspend(String, bool),
+    ConfirmDiskAutoSuspend(gtk::Switch, String),
+    SetDiskAutoSuspendDelay(gtk::ComboBoxText, String, String),
+    SetAudioPowerSave(i32),
+    SetAudioPowerSaveController(bool),
+    PersistAudio,
+    SetCpuGovernor(Option<String>, String),
+    SetCpuEpp(Option<String>, String),
+    WriteFile(gtk::MessageDialog, String, String),
     ShowPane(String),
+    SaveProfile(String),
+    LoadProfile(String),
+    DeleteProfile(String),
+    UpdateAutoSwitch,
+    PowerSourceChanged(bool),
+    StartMeasure(u32),
+    MeasureProgress(f64, String),
+    MeasureDone(std::result::Result<(Measurement, Measurement), String>),
+    ExportMeasure,
+    ShowWakeupDevice(String, String),
+    SetAcpiWakeup(String, bool),
 }
 
 pub struct GpInnerApplication {
@@ -73,21 +128,351 @@ pub struct GpInnerApplication {
     builder: RefCell<Option<gtk::Builder>>,
 }
 
+#[derive(Clone)]
 struct State {
     usb_devices: Vec<UsbDevice>,
     pci_devices: Vec<PciDevice>,
+    aspm_policy: Option<AspmPolicy>,
+    sata_hosts: Vec<SataHost>,
+    disks: Vec<Disk>,
+    audio: Option<AudioPowerSave>,
+    cpu_policies: Vec<CpuPolicy>,
+    platform_profile: Option<PlatformProfile>,
+    mem_sleep: Option<MemSleep>,
+    batteries: Vec<Battery>,
+    wakeup_sources: Vec<WakeupSource>,
+    acpi_wakeup: Vec<AcpiWakeupDevice>,
+    auto_switch: AutoSwitch,
+    /// The samples of the last measurement, before and after applying changes.
+    measurement: Option<(Measurement, Measurement)>,
+    /// Whether changes are being written, which can wait for authentication.
+    applying: bool,
+    /// Whether a measurement is running, with everything but its pane locked.
+    measuring: bool,
+    /// The power source that changed while applying or measuring, switched to
+    /// afterwards.
+    pending_power_source: Option<bool>,
     changed: bool,
     errors: u16,
 }
 
 impl State {
-    fn new(usb_devices: Vec<UsbDevice>, pci_devices: Vec<PciDevice>) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(State {
-            usb_devices,
-            pci_devices,
+    fn load() -> Rc<RefCell<Self>> {
+        let mut state = State {
+            usb_devices: Vec::new(),
+            pci_devices: Vec::new(),
+            aspm_policy: None,
+            sata_hosts: Vec::new(),
+            disks: Vec::new(),
+            audio: None,
+            cpu_policies: Vec::new(),
+            platform_profile: None,
+            mem_sleep: None,
+            batteries: Vec::new(),
+            wakeup_sources: Vec::new(),
+            acpi_wakeup: Vec::new(),
+            auto_switch: match profile::load_auto_switch() {
+                Ok(a) => a,
+                Err(e) => {
+                    error!("failed to load automatic switching: {}", e);
+                    AutoSwitch::default()
+                }
+            },
+            measurement: None,
+            applying: false,
+            measuring: false,
+            pending_power_source: None,
             changed: false,
             errors: 0,
-        }))
+        };
+        state.reload();
+
+        Rc::new(RefCell::new(state))
+    }
+
+    /// Reads everything again from the system, dropping any pending change.
+    fn reload(&mut self) {
+        self.usb_devices = match usb::list_devices() {
+            Ok(d) => d,
+            Err(e) => {
+                error!("failed to load devices: {}", e);
+                Vec::new()
+            }
+        };
+        self.pci_devices = match pci::list_devices() {
+            Ok(d) => d,
+            Err(e) => {
+                error!("failed to load devices: {}", e);
+                Vec::new()
+            }
+        };
+        self.aspm_policy = match pci::read_policy() {
+            Ok(p) => Some(p),
+            Err(e) => {
+                warn!("ASPM policy not available: {}", e);
+                None
+            }
+        };
+        self.sata_hosts = match sata::list_hosts() {
+            Ok(h) => h,
+            Err(e) => {
+                error!("failed to load sata hosts: {}", e);
+                Vec::new()
+            }
+        };
+        self.disks = match disk::list_disks() {
+            Ok(d) => d,
+            Err(e) => {
+                error!("failed to load disks: {}", e);
+                Vec::new()
+            }
+        };
+        self.audio = match audio::read_power_save() {
+            Ok(a) => Some(a),
+            Err(e) => {
+                warn!("audio power saving not available: {}", e);
+                None
+            }
+        };
+        self.cpu_policies = match cpu::list_policies() {
+            Ok(p) => p,
+            Err(e) => {
+                error!("failed to load cpufreq policies: {}", e);
+                Vec::new()
+            }
+        };
+        self.platform_profile = match platform::read_platform_profile() {
+            Ok(p) => Some(p),
+            Err(e) => {
+                warn!("platform profile not available: {}", e);
+                None
+            }
+        };
+        self.mem_sleep = match sleep::read_mem_sleep() {
+            Ok(m) => Some(m),
+            Err(e) => {
+                warn!("suspend mode not available: {}", e);
+                None
+            }
+        };
+        self.batteries = match power_supply::list_batteries() {
+            Ok(b) => b,
+            Err(e) => {
+                error!("failed to load batteries: {}", e);
+                Vec::new()
+            }
+        };
+        self.wakeup_sources = match wakeup::list_sources() {
+            Ok(s) => s,
+            Err(e) => {
+                error!("failed to load wakeup sources: {}", e);
+                Vec::new()
+            }
+        };
+        self.acpi_wakeup = match wakeup::read_acpi_wakeup() {
+            Ok(d) => d,
+            Err(e) => {
+                warn!("ACPI wakeup table not available: {}", e);
+                Vec::new()
+            }
+        };
+    }
+}
+
+impl State {
+    fn to_profile(&self, name: &str) -> Profile {
+        let mut profile = Profile::new(name);
+
+        if let Some(platform_profile) = &self.platform_profile {
+            let settings = vec![(
+                "platform_profile".to_owned(),
+                platform_profile.current().to_owned(),
+            )];
+            profile.add_section("platform", "", settings);
+        }
+        if let Some(mem_sleep) = &self.mem_sleep {
+            let settings = vec![("mem_sleep".to_owned(), mem_sleep.current().to_owned())];
+            profile.add_section("sleep", "", settings);
+        }
+
+        for d in &self.usb_devices {
+            let matcher = UsbMatcher::from(d.identity());
+            profile.add_section("usb", &matcher.to_string(), d.profile_settings());
+        }
+        for d in &self.pci_devices {
+            let matcher = PciMatcher::from(d.identity());
+            profile.add_section("pci", &matcher.to_string(), d.profile_settings());
+        }
+        if let Some(policy) = &self.aspm_policy {
+            let settings = vec![("policy".to_owned(), policy.current().to_owned())];
+            profile.add_section("aspm", "", settings);
+        }
+        for h in &self.sata_hosts {
+            profile.add_section("sata", &h.profile_target(), h.profile_settings());
+        }
+        for d in &self.disks {
+            profile.add_section("disk", d.profile_target(), d.profile_settings());
+        }
+        if let Some(audio) = &self.audio {
+            profile.add_section("audio", "", audio.profile_settings());
+        }
+        for p in &self.cpu_policies {
+            profile.add_section("cpu", p.get_id(), p.profile_settings());
+        }
+        for b in &self.batteries {
+            profile.add_section("battery", b.get_id(), b.profile_settings());
+        }
+        for d in &self.acpi_wakeup {
+            profile.add_section("acpi_wakeup", d.get_name(), d.profile_settings());
+        }
+
+        profile
+    }
+
+    fn apply_profile(&mut self, profile: &Profile) {
+        debug!("applying profile '{}'", profile.get_name());
+
+        for section in profile.sections() {
+            match section.kind.as_str() {
+                "usb" => match section.target.parse::<UsbMatcher>() {
+                    Ok(matcher) => {
+                        for d in self.usb_devices.iter_mut().filter(|d| d.matches(&matcher)) {
+                            for (key, value) in &section.settings {
+                                if let Err(e) = d.apply_profile_setting(key, value) {
+                                    warn!("ignoring setting for {}: {}", d.get_id(), e);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("ignoring usb section '{}': {}", section.target, e),
+                },
+                "pci" => match section.target.parse::<PciMatcher>() {
+                    Ok(matcher) => {
+                        for d in self.pci_devices.iter_mut().filter(|d| d.matches(&matcher)) {
+                            for (key, value) in &section.settings {
+                                if let Err(e) = d.apply_profile_setting(key, value) {
+                                    warn!("ignoring setting for {}: {}", d.get_id(), e);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("ignoring pci section '{}': {}", section.target, e),
+                },
+                "platform" => match &mut self.platform_profile {
+                    Some(platform_profile) => {
+                        for (key, value) in &section.settings {
+                            let res = match key.as_str() {
+                                "platform_profile" => platform_profile.set(value),
+                                _ => Err(anyhow!("unknown platform setting '{}'", key)),
+                            };
+                            if let Err(e) = res {
+                                warn!("ignoring platform setting: {}", e);
+                            }
+                        }
+                    }
+                    None => warn!("ignoring platform section, platform profile not available"),
+                },
+                "sleep" => match &mut self.mem_sleep {
+                    Some(mem_sleep) => {
+                        for (key, value) in &section.settings {
+                            let res = match key.as_str() {
+                                "mem_sleep" => mem_sleep.set(value),
+                                _ => Err(anyhow!("unknown sleep setting '{}'", key)),
+                            };
+                            if let Err(e) = res {
+                                warn!("ignoring sleep setting: {}", e);
+                            }
+                        }
+                    }
+                    None => warn!("ignoring sleep section, suspend mode not available"),
+                },
+                "aspm" => match &mut self.aspm_policy {
+                    Some(policy) => {
+                        for (key, value) in &section.settings {
+                            let res = match key.as_str() {
+                                "policy" => policy.set(value),
+                                _ => Err(anyhow!("unknown aspm setting '{}'", key)),
+                            };
+                            if let Err(e) = res {
+                                warn!("ignoring aspm setting: {}", e);
+                            }
+                        }
+                    }
+                    None => warn!("ignoring aspm section, ASPM policy not available"),
+                },
+                "sata" => {
+                    for h in self
+                        .sata_hosts
+                        .iter_mut()
+                        .filter(|h| h.profile_target() == section.target)
+                    {
+                        for (key, value) in &section.settings {
+                            if let Err(e) = h.apply_profile_setting(key, value) {
+                                warn!("ignoring setting for {}: {}", h.get_id(), e);
+                            }
+                        }
+                    }
+                }
+                "audio" => match &mut self.audio {
+                    Some(audio) => {
+                        for (key, value) in &section.settings {
+                            if let Err(e) = audio.apply_profile_setting(key, value) {
+                                warn!("ignoring audio setting: {}", e);
+                            }
+                        }
+                    }
+                    None => warn!("ignoring audio section, audio power saving not available"),
+                },
+                "cpu" => {
+                    let policies = self.cpu_policies.iter_mut();
+                    for p in policies.filter(|p| p.get_id() == section.target) {
+                        for (key, value) in &section.settings {
+                            if let Err(e) = p.apply_profile_setting(key, value) {
+                                warn!("ignoring setting for {}: {}", p.get_id(), e);
+                            }
+                        }
+                    }
+                }
+                "acpi_wakeup" => {
+                    let devices = self.acpi_wakeup.iter_mut();
+                    for d in devices.filter(|d| d.get_name() == section.target) {
+                        for (key, value) in &section.settings {
+                            if let Err(e) = d.apply_profile_setting(key, value) {
+                                warn!("ignoring setting for {}: {}", d.get_name(), e);
+                            }
+                        }
+                    }
+                }
+                "battery" => {
+                    let batteries = self.batteries.iter_mut();
+                    for b in batteries.filter(|b| b.get_id() == section.target) {
+                        // the thresholds only make sense together, a pair that
+                        // doesn't fit leaves both as they were
+                        let mut battery = b.clone();
+                        for (key, value) in &section.settings {
+                            if let Err(e) = battery.apply_profile_setting(key, value) {
+                                warn!("ignoring setting for {}: {}", b.get_id(), e);
+                            }
+                        }
+                        match battery.check_thresholds() {
+                            Ok(()) => *b = battery,
+                            Err(e) => warn!("ignoring thresholds for {}: {}", b.get_id(), e),
+                        }
+                    }
+                }
+                "disk" => {
+                    let disks = self.disks.iter_mut();
+                    for d in disks.filter(|d| d.profile_target() == section.target) {
+                        for (key, value) in &section.settings {
+                            if let Err(e) = d.apply_profile_setting(key, value) {
+                                warn!("ignoring setting for {}: {}", d.get_id(), e);
+                            }
+                        }
+                    }
+                }
+                kind => warn!("ignoring unknown profile section '{}'", kind),
+            }
+        }
     }
 }
 
@@ -120,6 +505,25 @@ impl GpInnerApplication {
         state.changed = false;
     }
 
+    /// Locks the other panes and the header buttons while measuring, so the
+    /// changes being measured can't be edited, applied or dropped halfway.
+    fn set_measuring(&self, measuring: bool) {
+        get_widget!(main_stack, gtk::Stack, @self);
+        get_widget!(refresh_button, gtk::Button, @self);
+        get_widget!(apply_button, gtk::Button, @self);
+
+        for pane in main_stack.get_children() {
+            if main_stack.get_child_name(&pane).as_deref() != Some("measure_pane") {
+                pane.set_sensitive(!measuring);
+            }
+        }
+        refresh_button.set_sensitive(!measuring);
+
+        let mut state = self.state.borrow_mut();
+        state.measuring = measuring;
+        apply_button.set_sensitive(!measuring && state.changed && state.errors == 0);
+    }
+
     fn populate_summary(&self) {
         get_widget!(
             label_usb_summary,
@@ -136,6 +540,11 @@ impl GpInnerApplication {
             gtk::Label,
             @self
         );
+        get_widget!(
+            label_aspm_summary,
+            gtk::Label,
+            @self
+        );
 
         let state = &self.state.borrow();
         let mut usb_suspendable_count = 0;
@@ -149,10 +558,20 @@ impl GpInnerApplication {
             }
         }
         let mut pci_suspendable_count = 0;
+        let mut l1_substates_count = 0;
+        let mut l1_substates_disabled_count = 0;
         for d in &state.pci_devices {
             if d.can_autosuspend() {
                 pci_suspendable_count += 1;
             }
+            if let Some(link) = d.link() {
+                if link.get(LinkState::L1_1).is_some() || link.get(LinkState::L1_2).is_some() {
+                    l1_substates_count += 1;
+                }
+                if link.has_l1_substates_disabled() {
+                    l1_substates_disabled_count += 1;
+                }
+            }
         }
 
         label_usb_summary.set_text(&format!(
@@ -170,7 +589,119 @@ impl GpInnerApplication {
             pci_suspendable_count,
             &state.pci_devices.len()
         ));
+        label_aspm_summary.set_text(&format!(
+            "{} / {}",
+            l1_substates_disabled_count, l1_substates_count
+        ));
+    }
+
+    fn fill_power_supply_box(&self) {
+        get_widget!(
+            power_supply_box,
+            gtk::Box,
+            @self
+        );
+        for child in power_supply_box.get_children() {
+            power_supply_box.remove(&child);
+        }
+
+        let ac_label = gtk::Label::new(None);
+        ac_label.set_halign(gtk::Align::Start);
+        ac_label.set_no_show_all(true);
+        power_supply_box.add(&ac_label);
+        let show_ac = clone!(@weak ac_label => @default-return glib::Continue(false), move || {
+            match power_supply::ac_online() {
+                Some(online) => {
+                    ac_label.set_text(if online { "On AC power" } else { "On battery" });
+                    ac_label.set_visible(true);
+                }
+                None => ac_label.set_visible(false),
+            }
+            glib::Continue(true)
+        });
+        show_ac();
+        glib::timeout_add_seconds_local(2, show_ac);
+
+        for battery in &self.state.borrow().batteries {
+            power_supply_box.add(&build_battery_card(battery));
+        }
+
+        power_supply_box.show_all();
+    }
+}
+
+/// A frame showing the charge and draw of a battery, kept up to date until it
+/// is destroyed.
+fn build_battery_card(battery: &Battery) -> gtk::Frame {
+    let frame = gtk::Frame::new(Some(&battery.get_name()));
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_margin_top(6);
+    grid.set_margin_bottom(6);
+    grid.set_margin_start(12);
+    grid.set_margin_end(12);
+    frame.add(&grid);
+
+    let mut labels = Vec::new();
+    for (line, name) in ["Charge", "Energy", "Power draw"].iter().enumerate() {
+        let name_label = gtk::Label::new(Some(name));
+        name_label.get_style_context().add_class("dim-label");
+        name_label.set_halign(gtk::Align::End);
+        let value_label = gtk::Label::new(None);
+        value_label.set_halign(gtk::Align::Start);
+        // both are hidden when the battery doesn't report the value
+        name_label.set_no_show_all(true);
+        value_label.set_no_show_all(true);
+        grid.attach(&name_label, 0, line as i32, 1, 1);
+        grid.attach(&value_label, 1, line as i32, 1, 1);
+        labels.push((name_label, value_label));
     }
+
+    let battery = battery.clone();
+    let refresh = clone!(@weak grid => @default-return glib::Continue(false), move || {
+        match battery.read() {
+            Ok(reading) => {
+                let texts = battery_reading_texts(&reading);
+                for ((name_label, value_label), text) in labels.iter().zip(texts) {
+                    name_label.set_visible(text.is_some());
+                    value_label.set_visible(text.is_some());
+                    value_label.set_text(text.as_deref().unwrap_or_default());
+                }
+                glib::Continue(true)
+            }
+            Err(e) => {
+                warn!("stopping refresh of battery: {}", e);
+                glib::Continue(false)
+            }
+        }
+    });
+    refresh();
+    glib::timeout_add_seconds_local(2, refresh);
+
+    frame
+}
+
+/// The charge, energy and power draw of a battery, as shown on its card.
+fn battery_reading_texts(reading: &BatteryReading) -> Vec<Option<String>> {
+    let charge = match (reading.capacity, &reading.status) {
+        (Some(capacity), Some(status)) => Some(format!("{}% ({})", capacity, status)),
+        (Some(capacity), None) => Some(format!("{}%", capacity)),
+        (None, status) => status.clone(),
+    };
+    let energy = match (reading.energy_now, reading.energy_full) {
+        (Some(now), Some(full)) => Some(format!(
+            "{:.1} / {:.1} Wh",
+            now as f64 / 1e6,
+            full as f64 / 1e6
+        )),
+        _ => None,
+    };
+    let power = reading
+        .power_now
+        .map(|p| format!("{:.1} W", p as f64 / 1e6));
+
+    vec![charge, energy, power]
 }
 
 impl ObjectSubclass for GpInnerApplication {
@@ -183,21 +714,7 @@ impl ObjectSubclass for GpInnerApplication {
 
     fn new() -> Self {
         debug!("initializing GPInnerApplication");
-        let usb_devices = match usb::list_devices() {
-            Ok(d) => d,
-            Err(e) => {
-                error!("failed to load devices: {}", e);
-                Vec::new()
-            }
-        };
-        let pci_devices = match pci::list_devices() {
-            Ok(d) => d,
-            Err(e) => {
-                error!("failed to load devices: {}", e);
-                Vec::new()
-            }
-        };
-        let state = State::new(usb_devices, pci_devices);
+        let state = State::load();
 
         let (sender, receiver) = MainContext::channel(glib::PRIORITY_DEFAULT);
 
@@ -222,6 +739,12 @@ impl ApplicationImpl for GpInnerApplication {
         let outer_app = ObjectSubclass::get_instance(self)
             .downcast::<GPApplication>()
             .unwrap();
+        // the window is only hidden when closed while switching profiles automatically
+        if let Some(win) = outer_app.get_windows().first() {
+            win.present();
+            return;
+        }
+
         let win = outer_app.create_window();
 
         win.show_all();
@@ -231,6 +754,10 @@ impl ApplicationImpl for GpInnerApplication {
             .take()
             .unwrap()
             .attach(None, move |action| outer_app.process_action(action));
+
+        power_supply::watch_power_source(clone!(@strong self.sender as sender => move |on_ac| {
+            activate!(sender, Action::PowerSourceChanged(on_ac));
+        }));
     }
 }
 
@@ -308,42 +835,22 @@ impl GPApplication {
         });
 
         get_widget!(category_list, gtk::ListBox, builder);
-        let label = gtk::Label::with_mnemonic(Some("_Summary"));
-        label.set_margin_top(6);
-        label.set_margin_bottom(6);
-        label.set_margin_start(18);
-        label.set_halign(gtk::Align::Start);
-        let summary_row = gtk::ListBoxRow::new();
-        summary_row.add(&label);
-        summary_row.set_action_name(Some("win.show_summary"));
-        category_list.add(&summary_row);
-        let label = gtk::Label::with_mnemonic(Some("_USB Autosuspend"));
-        label.set_margin_top(6);
-        label.set_margin_bottom(6);
-        label.set_margin_start(18);
-        label.set_halign(gtk::Align::Start);
-        let usb_row = gtk::ListBoxRow::new();
-        usb_row.add(&label);
-        usb_row.set_action_name(Some("win.show_usb"));
-        category_list.add(&usb_row);
-        let label2 = gtk::Label::with_mnemonic(Some("_USB Remote Wakeup"));
-        label2.set_margin_top(6);
-        label2.set_margin_bottom(6);
-        label2.set_margin_start(18);
-        label2.set_halign(gtk::Align::Start);
-        let usb_wakeup_row = gtk::ListBoxRow::new();
-        usb_wakeup_row.add(&label2);
-        usb_wakeup_row.set_action_name(Some("win.show_usb_wakeup"));
-        category_list.add(&usb_wakeup_row);
-        let label = gtk::Label::with_mnemonic(Some("_PCI Autosuspend"));
-        label.set_margin_top(6);
-        label.set_margin_bottom(6);
-        label.set_margin_start(18);
-        label.set_halign(gtk::Align::Start);
-        let pci_row = gtk::ListBoxRow::new();
-        pci_row.add(&label);
-        pci_row.set_action_name(Some("win.show_pci"));
-        category_list.add(&pci_row);
+        let summary_row = add_category(&category_list, "_Summary", "win.show_summary");
+        let usb_row = add_category(&category_list, "_USB Autosuspend", "win.show_usb");
+        let usb_wakeup_row =
+            add_category(&category_list, "_USB Remote Wakeup", "win.show_usb_wakeup");
+        let usb_auth_row = add_category(&category_list, "USB _Authorization", "win.show_usb_auth");
+        let pci_row = add_category(&category_list, "_PCI Autosuspend", "win.show_pci");
+        let aspm_row = add_category(&category_list, "PCIe _ASPM", "win.show_aspm");
+        let sata_row = add_category(&category_list, "SATA _Link Power", "win.show_sata");
+        let disks_row = add_category(&category_list, "_Disks", "win.show_disks");
+        let audio_row = add_category(&category_list, "Aud_io", "win.show_audio");
+        let cpu_row = add_category(&category_list, "_CPU Frequency", "win.show_cpu");
+        let battery_row = add_category(&category_list, "_Battery", "win.show_battery");
+        let wakeup_row = add_category(&category_list, "_Wakeup Sources", "win.show_wakeup");
+        let acpi_wakeup_row = add_category(&category_list, "AC_PI Wakeup", "win.show_acpi_wakeup");
+        let measure_row = add_category(&category_list, "_Measure", "win.show_measure");
+        let profiles_row = add_category(&category_list, "P_rofiles", "win.show_profiles");
 
         action!(
             win,
@@ -375,6 +882,16 @@ impl GPApplication {
             })
         );
 
+        action!(
+            win,
+            "show_usb_auth",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing usb authorization pane");
+                activate!(sender, Action::ShowPane("usb_auth_pane".to_owned()));
+                category_list.select_row(Some(&usb_auth_row));
+            })
+        );
+
         action!(
             win,
             "show_pci",
@@ -384,56 +901,382 @@ impl GPApplication {
                 category_list.select_row(Some(&pci_row));
             })
         );
-        get_widget!(main_usb_list_box, gtk::ListBox, builder);
-        get_widget!(main_usb_wakeup_list_box, gtk::ListBox, builder);
-        get_widget!(main_pci_list_box, gtk::ListBox, builder);
 
-        self.fill_list(
-            &main_usb_list_box,
-            &main_usb_wakeup_list_box,
-            &main_pci_list_box,
+        action!(
+            win,
+            "show_aspm",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing aspm pane");
+                activate!(sender, Action::ShowPane("aspm_pane".to_owned()));
+                category_list.select_row(Some(&aspm_row));
+            })
         );
 
-        get_widget!(usb_scroll, gtk::ScrolledWindow, builder);
-        usb_scroll.add(&main_usb_list_box);
-
-        get_widget!(usb_wakeup_scroll, gtk::ScrolledWindow, builder);
-        usb_wakeup_scroll.add(&main_usb_wakeup_list_box);
+        action!(
+            win,
+            "show_sata",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing sata pane");
+                activate!(sender, Action::ShowPane("sata_pane".to_owned()));
+                category_list.select_row(Some(&sata_row));
+            })
+        );
 
-        get_widget!(pci_scroll, gtk::ScrolledWindow, builder);
-        pci_scroll.add(&main_pci_list_box);
+        action!(
+            win,
+            "show_disks",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing disks pane");
+                activate!(sender, Action::ShowPane("disks_pane".to_owned()));
+                category_list.select_row(Some(&disks_row));
+            })
+        );
 
-        inner.builder.replace(Some(builder));
+        action!(
+            win,
+            "show_audio",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing audio pane");
+                activate!(sender, Action::ShowPane("audio_pane".to_owned()));
+                category_list.select_row(Some(&audio_row));
+            })
+        );
 
-        inner.populate_summary();
+        action!(
+            win,
+            "show_cpu",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing cpu pane");
+                activate!(sender, Action::ShowPane("cpu_pane".to_owned()));
+                category_list.select_row(Some(&cpu_row));
+            })
+        );
 
-        win
-    }
+        action!(
+            win,
+            "show_battery",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing battery pane");
+                activate!(sender, Action::ShowPane("battery_pane".to_owned()));
+                category_list.select_row(Some(&battery_row));
+            })
+        );
 
-    fn fill_list(
-        &self,
-        main_usb_list_box: &gtk::ListBox,
-        main_usb_wakeup_list_box: &gtk::ListBox,
-        main_pci_list_box: &gtk::ListBox,
-    ) {
-        let inner = GpInnerApplication::from_instance(self);
+        action!(
+            win,
+            "show_wakeup",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing wakeup pane");
+                activate!(sender, Action::ShowPane("wakeup_pane".to_owned()));
+                category_list.select_row(Some(&wakeup_row));
+            })
+        );
 
-        let mut entries = Vec::new();
-        for d in inner.state.borrow().usb_devices.iter() {
-            entries.push(self.build_usb_entry(&d, inner));
-        }
-        for e in entries {
-            main_usb_list_box.add(&e);
-        }
+        action!(
+            win,
+            "show_acpi_wakeup",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing acpi wakeup pane");
+                activate!(sender, Action::ShowPane("acpi_wakeup_pane".to_owned()));
+                category_list.select_row(Some(&acpi_wakeup_row));
+            })
+        );
 
-        let mut entries = Vec::new();
-        for d in inner.state.borrow().usb_devices.iter() {
-            entries.push(self.build_usb_wakeup_entry(&d, inner));
+        action!(
+            win,
+            "show_measure",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing measure pane");
+                activate!(sender, Action::ShowPane("measure_pane".to_owned()));
+                category_list.select_row(Some(&measure_row));
+            })
+        );
+
+        get_widget!(measure_duration_spin, gtk::SpinButton, builder);
+        measure_duration_spin.set_range(10.0, 1800.0);
+        measure_duration_spin.set_increments(10.0, 60.0);
+        measure_duration_spin.set_value(60.0);
+        action!(
+            win,
+            "start_measure",
+            clone!(@strong inner.sender as sender, @strong measure_duration_spin => move |_,_| {
+                let seconds = measure_duration_spin.get_value_as_int() as u32;
+                activate!(sender, Action::StartMeasure(seconds));
+            })
+        );
+        action!(
+            win,
+            "export_measure",
+            clone!(@strong inner.sender as sender => move |_,_| {
+                activate!(sender, Action::ExportMeasure);
+            })
+        );
+
+        action!(
+            win,
+            "persist_audio",
+            clone!(@strong inner.sender as sender => move |_,_| {
+                activate!(sender, Action::PersistAudio);
+            })
+        );
+        action!(
+            win,
+            "persist_mem_sleep",
+            clone!(@strong inner.sender as sender => move |_,_| {
+                activate!(sender, Action::PersistMemSleep);
+            })
+        );
+        action!(
+            win,
+            "show_profiles",
+            clone!(@strong inner.sender as sender, @strong category_list => move |_,_| {
+                debug!("showing profiles pane");
+                activate!(sender, Action::ShowPane("profiles_pane".to_owned()));
+                category_list.select_row(Some(&profiles_row));
+            })
+        );
+
+        get_widget!(profile_name_entry, gtk::Entry, builder);
+        action!(
+            win,
+            "save_profile",
+            clone!(@strong inner.sender as sender, @strong profile_name_entry => move |_,_| {
+                let name = profile_name_entry.get_text().trim().to_owned();
+                if !name.is_empty() {
+                    debug!("saving profile '{}'", name);
+                    activate!(sender, Action::SaveProfile(name));
+                    profile_name_entry.set_text("");
+                }
+            })
+        );
+        get_widget!(save_profile_button, gtk::Button, builder);
+        profile_name_entry.connect_activate(move |_| save_profile_button.clicked());
+
+        get_widget!(auto_switch_switch, gtk::Switch, builder);
+        get_widget!(auto_switch_ac_combo, gtk::ComboBoxText, builder);
+        get_widget!(auto_switch_battery_combo, gtk::ComboBoxText, builder);
+        let enabled = inner.state.borrow().auto_switch.enabled;
+        auto_switch_switch.set_active(enabled);
+        auto_switch_ac_combo.set_sensitive(enabled);
+        auto_switch_battery_combo.set_sensitive(enabled);
+        auto_switch_switch.connect_property_active_notify(
+            clone!(@strong inner.sender as sender => move |_| {
+                activate!(sender, Action::UpdateAutoSwitch);
+            }),
+        );
+        for combo in &[auto_switch_ac_combo, auto_switch_battery_combo] {
+            combo.connect_changed(clone!(@strong inner.sender as sender => move |_| {
+                activate!(sender, Action::UpdateAutoSwitch);
+            }));
+        }
+
+        // keep running in the background to switch profiles
+        win.connect_delete_event(clone!(@strong inner.state as state => move |win, _| {
+            if state.borrow().auto_switch.enabled {
+                debug!("hiding main window");
+                win.hide();
+                Inhibit(true)
+            } else {
+                Inhibit(false)
+            }
+        }));
+
+        get_widget!(main_usb_list_box, gtk::ListBox, builder);
+        get_widget!(main_usb_wakeup_list_box, gtk::ListBox, builder);
+        get_widget!(main_usb_auth_list_box, gtk::ListBox, builder);
+        get_widget!(main_pci_list_box, gtk::ListBox, builder);
+        get_widget!(main_aspm_list_box, gtk::ListBox, builder);
+        get_widget!(main_sata_list_box, gtk::ListBox, builder);
+        get_widget!(main_disks_list_box, gtk::ListBox, builder);
+        get_widget!(main_audio_list_box, gtk::ListBox, builder);
+        get_widget!(main_cpu_list_box, gtk::ListBox, builder);
+        get_widget!(main_battery_list_box, gtk::ListBox, builder);
+        get_widget!(main_measure_list_box, gtk::ListBox, builder);
+        get_widget!(main_wakeup_list_box, gtk::ListBox, builder);
+        get_widget!(main_acpi_wakeup_list_box, gtk::ListBox, builder);
+
+        for list_box in &[
+            &main_usb_list_box,
+            &main_usb_wakeup_list_box,
+            &main_usb_auth_list_box,
+        ] {
+            list_box.connect_row_activated(clone!(@strong inner.sender as sender => move |_, row| {
+                activate!(sender, Action::ShowUsbDetails(row.clone(), row.get_widget_name().to_string()));
+            }));
+        }
+
+        get_widget!(usb_scroll, gtk::ScrolledWindow, builder);
+        usb_scroll.add(&main_usb_list_box);
+
+        get_widget!(usb_wakeup_scroll, gtk::ScrolledWindow, builder);
+        usb_wakeup_scroll.add(&main_usb_wakeup_list_box);
+
+        get_widget!(usb_auth_scroll, gtk::ScrolledWindow, builder);
+        usb_auth_scroll.add(&main_usb_auth_list_box);
+
+        get_widget!(pci_driver_filter_combo, gtk::ComboBoxText, builder);
+        main_pci_list_box.set_filter_func(Some(Box::new(
+            clone!(@strong inner.state as state, @strong pci_driver_filter_combo => move |row| {
+                let filter = match pci_driver_filter_combo.get_active_id() {
+                    Some(filter) if filter != ALL_DRIVERS => filter,
+                    _ => return true,
+                };
+                let id = row.get_widget_name();
+                state
+                    .borrow()
+                    .pci_devices
+                    .iter()
+                    .find(|d| d.get_id() == id.as_str())
+                    .map(|d| d.driver().unwrap_or(NO_DRIVER) == filter.as_str())
+                    .unwrap_or(true)
+            }),
+        )));
+        pci_driver_filter_combo.connect_changed(clone!(@strong main_pci_list_box => move |_| {
+            main_pci_list_box.invalidate_filter();
+        }));
+
+        main_pci_list_box.connect_row_activated(clone!(@strong inner.sender as sender => move |_, row| {
+            activate!(sender, Action::ShowPciDetails(row.clone(), row.get_widget_name().to_string()));
+        }));
+
+        get_widget!(pci_scroll, gtk::ScrolledWindow, builder);
+        pci_scroll.add(&main_pci_list_box);
+
+        get_widget!(aspm_scroll, gtk::ScrolledWindow, builder);
+        aspm_scroll.add(&main_aspm_list_box);
+
+        get_widget!(sata_scroll, gtk::ScrolledWindow, builder);
+        sata_scroll.add(&main_sata_list_box);
+
+        get_widget!(disks_scroll, gtk::ScrolledWindow, builder);
+        disks_scroll.add(&main_disks_list_box);
+
+        get_widget!(audio_scroll, gtk::ScrolledWindow, builder);
+        audio_scroll.add(&main_audio_list_box);
+
+        get_widget!(cpu_scroll, gtk::ScrolledWindow, builder);
+        cpu_scroll.add(&main_cpu_list_box);
+
+        get_widget!(battery_scroll, gtk::ScrolledWindow, builder);
+        battery_scroll.add(&main_battery_list_box);
+
+        get_widget!(wakeup_scroll, gtk::ScrolledWindow, builder);
+        wakeup_scroll.add(&main_wakeup_list_box);
+
+        get_widget!(acpi_wakeup_scroll, gtk::ScrolledWindow, builder);
+        acpi_wakeup_scroll.add(&main_acpi_wakeup_list_box);
+
+        get_widget!(measure_scroll, gtk::ScrolledWindow, builder);
+        measure_scroll.add(&main_measure_list_box);
+        get_widget!(measure_progress, gtk::ProgressBar, builder);
+        measure_progress.set_text(Some("Not measured yet"));
+
+        get_widget!(aspm_policy_box, gtk::Box, builder);
+        get_widget!(aspm_policy_combo, gtk::ComboBoxText, builder);
+        if let Some(policy) = &inner.state.borrow().aspm_policy {
+            for p in policy.available() {
+                aspm_policy_combo.append(Some(p), AspmPolicy::get_description(p));
+            }
+            aspm_policy_combo.set_active_id(Some(policy.current()));
+        } else {
+            aspm_policy_box.set_visible(false);
+            aspm_policy_box.set_no_show_all(true);
+        }
+        aspm_policy_combo.connect_changed(clone!(@strong inner.sender as sender => move |cb| {
+            if let Some(policy) = cb.get_active_id() {
+                activate!(sender, Action::SetAspmPolicy(policy.to_string()));
+            }
+        }));
+
+        get_widget!(platform_profile_box, gtk::Box, builder);
+        get_widget!(platform_profile_combo, gtk::ComboBoxText, builder);
+        if let Some(platform_profile) = &inner.state.borrow().platform_profile {
+            for p in platform_profile.choices() {
+                platform_profile_combo.append(Some(p), &PlatformProfile::get_description(p));
+            }
+            platform_profile_combo.set_active_id(Some(platform_profile.current()));
+        } else {
+            platform_profile_box.set_visible(false);
+            platform_profile_box.set_no_show_all(true);
+        }
+        platform_profile_combo.connect_changed(
+            clone!(@strong inner.sender as sender => move |cb| {
+                if let Some(profile) = cb.get_active_id() {
+                    activate!(sender, Action::SetPlatformProfile(profile.to_string()));
+                }
+            }),
+        );
+
+        get_widget!(mem_sleep_box, gtk::Box, builder);
+        get_widget!(mem_sleep_combo, gtk::ComboBoxText, builder);
+        if let Some(mem_sleep) = &inner.state.borrow().mem_sleep {
+            for m in mem_sleep.available() {
+                mem_sleep_combo.append(Some(m), MemSleep::get_description(m));
+            }
+            mem_sleep_combo.set_active_id(Some(mem_sleep.current()));
+        } else {
+            mem_sleep_box.set_visible(false);
+            mem_sleep_box.set_no_show_all(true);
+        }
+        mem_sleep_combo.connect_changed(clone!(@strong inner.sender as sender => move |cb| {
+            if let Some(mode) = cb.get_active_id() {
+                activate!(sender, Action::SetMemSleep(mode.to_string()));
+            }
+        }));
+
+        get_widget!(main_profile_list_box, gtk::ListBox, builder);
+        get_widget!(profile_scroll, gtk::ScrolledWindow, builder);
+        profile_scroll.add(&main_profile_list_box);
+
+        inner.builder.replace(Some(builder));
+
+        self.fill_list();
+        self.fill_pci_driver_filter();
+        inner.populate_summary();
+        inner.fill_power_supply_box();
+        self.fill_profile_list();
+
+        win
+    }
+
+    fn fill_list(&self) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(main_usb_list_box, gtk::ListBox, @inner);
+        get_widget!(main_usb_wakeup_list_box, gtk::ListBox, @inner);
+        get_widget!(main_usb_auth_list_box, gtk::ListBox, @inner);
+        get_widget!(main_pci_list_box, gtk::ListBox, @inner);
+        get_widget!(main_aspm_list_box, gtk::ListBox, @inner);
+        get_widget!(main_sata_list_box, gtk::ListBox, @inner);
+        get_widget!(main_disks_list_box, gtk::ListBox, @inner);
+        get_widget!(main_audio_list_box, gtk::ListBox, @inner);
+        get_widget!(main_battery_list_box, gtk::ListBox, @inner);
+        get_widget!(main_wakeup_list_box, gtk::ListBox, @inner);
+        get_widget!(main_acpi_wakeup_list_box, gtk::ListBox, @inner);
+
+        let mut entries = Vec::new();
+        for d in inner.state.borrow().usb_devices.iter() {
+            entries.push(self.build_usb_entry(&d, inner));
+        }
+        for e in entries {
+            main_usb_list_box.add(&e);
+        }
+
+        let mut entries = Vec::new();
+        for d in inner.state.borrow().usb_devices.iter() {
+            entries.push(self.build_usb_wakeup_entry(&d, inner));
         }
         for e in entries {
             main_usb_wakeup_list_box.add(&e);
         }
 
+        let mut entries = Vec::new();
+        for d in inner.state.borrow().usb_devices.iter() {
+            entries.push(self.build_usb_auth_entry(d, inner));
+        }
+        for e in entries {
+            main_usb_auth_list_box.add(&e);
+        }
+
         let mut entries = Vec::new();
         for d in inner.state.borrow().pci_devices.iter() {
             entries.push(self.build_pci_entry(&d, inner));
@@ -441,6 +1284,86 @@ impl GPApplication {
         for e in entries {
             main_pci_list_box.add(&e);
         }
+
+        let mut entries = Vec::new();
+        for d in inner.state.borrow().pci_devices.iter() {
+            if let Some(link) = d.link() {
+                entries.push(self.build_aspm_entry(d, link, inner));
+            }
+        }
+        for e in entries {
+            main_aspm_list_box.add(&e);
+        }
+
+        let mut entries = Vec::new();
+        {
+            let state = inner.state.borrow();
+            for h in state.sata_hosts.iter() {
+                let controller = h
+                    .pci_slot()
+                    .and_then(|slot| state.pci_devices.iter().find(|d| d.identity().slot == slot));
+                entries.push(self.build_sata_entry(h, controller, inner));
+            }
+        }
+        for e in entries {
+            main_sata_list_box.add(&e);
+        }
+
+        let mut entries = Vec::new();
+        for d in inner.state.borrow().disks.iter() {
+            entries.push(self.build_disk_entry(d, inner));
+        }
+        for e in entries {
+            main_disks_list_box.add(&e);
+        }
+
+        get_widget!(audio_persist_box, gtk::Box, @inner);
+        let entries = match &inner.state.borrow().audio {
+            Some(audio) => self.build_audio_entries(audio, inner),
+            None => {
+                let row = gtk::ListBoxRow::new();
+                row.add(&make_unavailable_label(
+                    "The HDA audio driver is not loaded",
+                ));
+                vec![row]
+            }
+        };
+        audio_persist_box.set_sensitive(inner.state.borrow().audio.is_some());
+        for e in entries {
+            main_audio_list_box.add(&e);
+        }
+
+        self.fill_cpu_list();
+
+        let mut entries = Vec::new();
+        for b in inner.state.borrow().batteries.iter() {
+            entries.push(self.build_battery_entry(b, inner));
+        }
+        if entries.is_empty() {
+            let row = gtk::ListBoxRow::new();
+            row.add(&make_unavailable_label("No battery found"));
+            entries.push(row);
+        }
+        for e in entries {
+            main_battery_list_box.add(&e);
+        }
+
+        for s in inner.state.borrow().wakeup_sources.iter() {
+            main_wakeup_list_box.add(&self.build_wakeup_entry(s, inner));
+        }
+
+        let mut entries = Vec::new();
+        for d in inner.state.borrow().acpi_wakeup.iter() {
+            entries.push(self.build_acpi_wakeup_entry(d, inner));
+        }
+        if entries.is_empty() {
+            let row = gtk::ListBoxRow::new();
+            row.add(&make_unavailable_label("No ACPI wakeup table"));
+            entries.push(row);
+        }
+        for e in entries {
+            main_acpi_wakeup_list_box.add(&e);
+        }
     }
 
     fn make_usb_description(&self, device: &UsbDevice) -> gtk::Box {
@@ -469,23 +1392,305 @@ impl GPApplication {
         text_box
     }
 
-    fn build_usb_entry(&self, device: &UsbDevice, app: &GpInnerApplication) -> gtk::ListBoxRow {
-        let row = gtk::ListBoxRow::new();
-        row.set_can_focus(false);
-        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
-        let text_box = self.make_usb_description(device);
+    fn make_warning_icon(&self, reason: &str) -> gtk::Image {
+        let icon =
+            gtk::Image::from_icon_name(Some("dialog-warning-symbolic"), gtk::IconSize::Button);
+        icon.set_tooltip_text(Some(reason));
+        icon.set_valign(gtk::Align::Center);
+        icon.get_style_context().add_class("dim-label");
 
-        main_box.pack_start(&text_box, true, true, 0);
+        icon
+    }
+
+    fn build_usb_details(&self, device: &UsbDevice, app: &GpInnerApplication) -> gtk::Grid {
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.set_margin_top(12);
+        grid.set_margin_bottom(12);
+        grid.set_margin_start(12);
+        grid.set_margin_end(12);
+
+        let identity = device.identity();
+        let title = gtk::Label::new(Some(&device.get_name()));
+        title.get_style_context().add_class("type_label");
+        title.set_halign(gtk::Align::Start);
+        grid.attach(&title, 0, 0, 2, 1);
+
+        let mut line = 1;
+        let mut add_line = |name: &str, value: Option<&str>| {
+            add_detail_row(&grid, line, name, value.unwrap_or("Unknown"));
+            line += 1;
+        };
+        add_line(
+            "ID",
+            Some(&format!(
+                "{:04x}:{:04x}",
+                identity.vendor_id, identity.product_id
+            )),
+        );
+        add_line("Serial", identity.serial.as_deref());
+        add_line("Speed", device.speed().as_deref());
+        add_line("Max power", device.max_power());
+        add_line("Removable", device.removable());
+        add_line("USB version", device.version());
+        add_line(
+            "Bus / device",
+            Some(&format!(
+                "{:03} / {}",
+                identity.busnum,
+                device
+                    .devnum()
+                    .map(|n| format!("{:03}", n))
+                    .unwrap_or_else(|| "?".to_owned())
+            )),
+        );
+        add_line("Port", Some(&identity.port()));
+        add_line(
+            "Device node",
+            device
+                .char_device_path()
+                .map(|p| p.to_string_lossy())
+                .as_deref(),
+        );
+
+        let label = gtk::Label::new(Some("Link Power Management"));
+        label.get_style_context().add_class("type_label");
+        label.set_halign(gtk::Align::Start);
+        label.set_margin_top(6);
+        grid.attach(&label, 0, line, 2, 1);
+        line += 1;
+
+        let lpm_name = gtk::Label::new(Some("USB 2 LPM"));
+        lpm_name.get_style_context().add_class("dim-label");
+        lpm_name.set_halign(gtk::Align::End);
+        grid.attach(&lpm_name, 0, line, 1, 1);
+        if let Some(lpm) = device.usb2_hardware_lpm() {
+            let button = gtk::Switch::new();
+            button.set_active(lpm);
+            button.set_halign(gtk::Align::Start);
+            let id = device.get_id().to_owned();
+            button.connect_state_set(clone!(@strong app.sender as sender => move |_, on| {
+                activate!(sender, Action::SetUsbHardwareLpm(id.clone(), on));
+                glib::signal::Inhibit(false)
+            }));
+            grid.attach(&button, 1, line, 1, 1);
+        } else {
+            grid.attach(&make_unsupported_label(), 1, line, 1, 1);
+        }
+        line += 1;
+
+        for (name, state) in &[
+            ("USB 3 U1", device.usb3_hardware_lpm_u1()),
+            ("USB 3 U2", device.usb3_hardware_lpm_u2()),
+        ] {
+            match state {
+                Some(enabled) => {
+                    let text = if *enabled { "Enabled" } else { "Disabled" };
+                    add_detail_row(&grid, line, name, text);
+                }
+                None => {
+                    let name_label = gtk::Label::new(Some(name));
+                    name_label.get_style_context().add_class("dim-label");
+                    name_label.set_halign(gtk::Align::End);
+                    grid.attach(&name_label, 0, line, 1, 1);
+                    grid.attach(&make_unsupported_label(), 1, line, 1, 1);
+                }
+            }
+            line += 1;
+        }
+
+        let persist_name = gtk::Label::new(Some("Persist"));
+        persist_name.get_style_context().add_class("dim-label");
+        persist_name.set_halign(gtk::Align::End);
+        persist_name.set_tooltip_text(Some(
+            "Keep the device usable across a suspend where it loses power",
+        ));
+        grid.attach(&persist_name, 0, line, 1, 1);
+        if let Some(persist) = device.persist() {
+            let button = gtk::Switch::new();
+            button.set_active(persist);
+            button.set_halign(gtk::Align::Start);
+            let id = device.get_id().to_owned();
+            button.connect_state_set(clone!(@strong app.sender as sender => move |switch, on| {
+                // putting the switch back after a cancelled confirmation isn't a change
+                if switch.get_state() == on {
+                    return glib::signal::Inhibit(false);
+                }
+
+                if on {
+                    activate!(sender, Action::SetUsbPersist(id.clone(), true));
+                    glib::signal::Inhibit(false)
+                } else {
+                    activate!(sender, Action::ConfirmUsbPersistOff(switch.clone(), id.clone()));
+                    glib::signal::Inhibit(true)
+                }
+            }));
+            grid.attach(&button, 1, line, 1, 1);
+        } else {
+            grid.attach(&make_unsupported_label(), 1, line, 1, 1);
+        }
+        line += 1;
+
+        if !device.interfaces().is_empty() {
+            let label = gtk::Label::new(Some("Interfaces"));
+            label.get_style_context().add_class("type_label");
+            label.set_halign(gtk::Align::Start);
+            label.set_margin_top(6);
+            grid.attach(&label, 0, line, 2, 1);
+            line += 1;
+
+            for interface in device.interfaces() {
+                let label = gtk::Label::new(Some(&interface.get_description()));
+                label.get_style_context().add_class("desc_label");
+                label.set_halign(gtk::Align::Start);
+                grid.attach(&label, 0, line, 2, 1);
+                line += 1;
+            }
+        }
+
+        grid
+    }
+
+    fn build_pci_details(&self, device: &PciDevice) -> gtk::Grid {
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.set_margin_top(12);
+        grid.set_margin_bottom(12);
+        grid.set_margin_start(12);
+        grid.set_margin_end(12);
+
+        let identity = device.identity();
+        let title = gtk::Label::new(Some(&device.get_name()));
+        title.get_style_context().add_class("type_label");
+        title.set_halign(gtk::Align::Start);
+        grid.attach(&title, 0, 0, 2, 1);
+
+        let mut line = 1;
+        add_detail_row(&grid, line, "Slot", &identity.slot);
+        line += 1;
+        add_detail_row(
+            &grid,
+            line,
+            "ID",
+            &format!("{:04x}:{:04x}", identity.vendor_id, identity.device_id),
+        );
+        line += 1;
+        if let Some(status) = device.link_status() {
+            add_detail_row(
+                &grid,
+                line,
+                "Link speed",
+                &format!(
+                    "{} (max {})",
+                    status.current_speed().unwrap_or("Unknown"),
+                    status.max_speed().unwrap_or("Unknown")
+                ),
+            );
+            line += 1;
+            let width = |w: Option<u8>| w.map(|w| format!("x{}", w));
+            add_detail_row(
+                &grid,
+                line,
+                "Link width",
+                &format!(
+                    "{} (max {})",
+                    width(status.current_width())
+                        .as_deref()
+                        .unwrap_or("Unknown"),
+                    width(status.max_width()).as_deref().unwrap_or("Unknown")
+                ),
+            );
+            line += 1;
+            if let Some(warning) = device.link_warning() {
+                let label = gtk::Label::new(Some(&warning));
+                label.get_style_context().add_class("unsupported_label");
+                label.set_halign(gtk::Align::Start);
+                grid.attach(&label, 0, line, 2, 1);
+                line += 1;
+            }
+        }
+
+        let label = gtk::Label::new(Some("Capabilities"));
+        label.get_style_context().add_class("type_label");
+        label.set_halign(gtk::Align::Start);
+        label.set_margin_top(6);
+        grid.attach(&label, 0, line, 2, 1);
+        line += 1;
+
+        match device.capabilities() {
+            Ok(Some(caps)) => add_capability_rows(&grid, line, &caps),
+            Ok(None) => {
+                let button = gtk::Button::with_mnemonic("_Read as Administrator");
+                button.set_tooltip_text(Some(
+                    "Only root can read the capabilities from the configuration space",
+                ));
+                button.set_halign(gtk::Align::Start);
+                let device = device.clone();
+                button.connect_clicked(clone!(@weak grid => move |button| {
+                    button.set_sensitive(false);
+                    let device = device.clone();
+                    let button = button.clone();
+                    glib::MainContext::default().spawn_local(async move {
+                        let caps = device.capabilities_privileged().await;
+                        unsafe {
+                            button.destroy();
+                        }
+                        match caps {
+                            Ok(caps) => add_capability_rows(&grid, line, &caps),
+                            Err(e) => grid.attach(&make_error_label(&e), 0, line, 2, 1),
+                        }
+                        grid.show_all();
+                    });
+                }));
+                grid.attach(&button, 0, line, 2, 1);
+            }
+            Err(e) => grid.attach(&make_error_label(&e), 0, line, 2, 1),
+        }
+
+        grid
+    }
+
+    fn show_details_popover(&self, row: &gtk::ListBoxRow, content: &gtk::Grid) {
+        let popover = gtk::Popover::new(Some(row));
+        popover.add(content);
+        popover.connect_closed(|popover| {
+            // popovers are rebuilt every time, so the old one can go
+            unsafe {
+                popover.destroy();
+            }
+        });
+        content.show_all();
+        popover.popup();
+    }
+
+    /// Adds the autosuspend switch and delay combo shared by all device lists.
+    ///
+    /// The actions to send when they change are made by `set_autosuspend` and
+    /// `set_delay`.
+    fn add_autosuspend_controls<S, D>(
+        &self,
+        main_box: &gtk::Box,
+        autosuspend: bool,
+        delay: u64,
+        app: &GpInnerApplication,
+        set_autosuspend: S,
+        set_delay: D,
+    ) -> gtk::Switch
+    where
+        S: Fn(bool) -> Action + 'static,
+        D: Fn(gtk::ComboBoxText, String) -> Action + 'static,
+    {
+        let set_delay = Rc::new(set_delay);
         let cb_box = gtk::ComboBoxText::with_entry();
         let button = gtk::Switch::new();
-        button.set_active(device.can_autosuspend());
-        let id = device.get_id();
+        button.set_active(autosuspend);
         button.connect_state_set(
-            clone!(@strong app.sender as sender, @strong cb_box as cb, @strong self as app => move |_, on| {
-                activate!(sender, Action::SetUsbAutoSuspend(id, on));
+            clone!(@strong app.sender as sender, @strong cb_box as cb, @strong self as app, @strong set_delay => move |_, on| {
+                activate!(sender, set_autosuspend(on));
                 if on {
-                    activate!(sender, Action::SetUsbAutoSuspendDelay(cb.clone(),
-                    id,
+                    activate!(sender, set_delay(cb.clone(),
                     cb.get_active_text().map(|s| s.as_str().to_owned()).unwrap_or_else(String::new),
                 ));
                 } else {
@@ -499,8 +1704,6 @@ impl GPApplication {
         main_box.add(&button);
         cb_box.set_valign(gtk::Align::Center);
         cb_box.append_text("0 seconds");
-        let delay = device.delay();
-        let autosuspend = device.can_autosuspend();
         cb_box.set_sensitive(autosuspend);
 
         if autosuspend && delay != 0 {
@@ -517,8 +1720,7 @@ impl GPApplication {
         cb_box.append_text("1 minute");
         cb_box.append_text("5 minutes");
         cb_box.connect_changed(clone!(@strong app.sender as sender => move |cb| {
-            activate!(sender, Action::SetUsbAutoSuspendDelay(cb.clone(),
-                id,
+            activate!(sender, set_delay(cb.clone(),
                 cb.get_active_text().map(|s| s.as_str().to_owned()).unwrap_or_else(String::new),
             ));
         }));
@@ -531,6 +1733,31 @@ impl GPApplication {
                     | glib::BindingFlags::BIDIRECTIONAL,
             )
             .build();
+
+        button
+    }
+
+    fn build_usb_entry(&self, device: &UsbDevice, app: &GpInnerApplication) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        row.set_activatable(true);
+        row.set_widget_name(device.get_id());
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = self.make_usb_description(device);
+
+        main_box.pack_start(&text_box, true, true, 0);
+        let id = device.get_id().to_owned();
+        if let Some(reason) = device.autosuspend_unsupported_reason() {
+            main_box.add(&self.make_warning_icon(&reason));
+        }
+        self.add_autosuspend_controls(
+            &main_box,
+            device.can_autosuspend(),
+            device.delay(),
+            app,
+            clone!(@strong id => move |on| Action::SetUsbAutoSuspend(id.clone(), on)),
+            move |cb, delay| Action::SetUsbAutoSuspendDelay(cb, id.clone(), delay),
+        );
         row.add(&main_box);
         row
     }
@@ -542,15 +1769,17 @@ impl GPApplication {
     ) -> gtk::ListBoxRow {
         let row = gtk::ListBoxRow::new();
         row.set_can_focus(false);
+        row.set_activatable(true);
+        row.set_widget_name(device.get_id());
         let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
         let text_box = self.make_usb_description(device);
 
         let button = gtk::Switch::new();
         button.set_sensitive(device.allow_wakeup().is_some());
         button.set_active(device.allow_wakeup().unwrap_or(false));
-        let id = device.get_id();
+        let id = device.get_id().to_owned();
         button.connect_state_set(clone!(@strong app.sender as sender => move |_, on| {
-            activate!(sender, Action::SetUsbAllowWakeup(id, on));
+            activate!(sender, Action::SetUsbAllowWakeup(id.clone(), on));
             glib::signal::Inhibit(false)
         }
         ));
@@ -563,106 +1792,1239 @@ impl GPApplication {
         row
     }
 
-    fn build_pci_entry(&self, device: &PciDevice, app: &GpInnerApplication) -> gtk::ListBoxRow {
+    fn build_usb_auth_entry(
+        &self,
+        device: &UsbDevice,
+        app: &GpInnerApplication,
+    ) -> gtk::ListBoxRow {
         let row = gtk::ListBoxRow::new();
         row.set_can_focus(false);
+        row.set_activatable(true);
+        row.set_widget_name(device.get_id());
         let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
-        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
-        let desc_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
-        let label_main = gtk::Label::new(Some(&device.get_name()));
-        let label_type = gtk::Label::new(Some(&device.get_kind_description()));
-        let label_info = gtk::Label::new(Some(&device.get_description()));
+        let text_box = self.make_usb_description(device);
+        main_box.pack_start(&text_box, true, true, 0);
+        let id = device.get_id().to_owned();
 
-        label_info.get_style_context().add_class("desc_label");
-        label_info.get_style_context().add_class("dim-label");
-        label_type.get_style_context().add_class("type_label");
-        label_type.get_style_context().add_class("desc_label");
-        label_type.get_style_context().add_class("dim-label");
+        if let Some(authorized_default) = device.authorized_default() {
+            let cb_box = gtk::ComboBoxText::new();
+            for policy in &[
+                UsbAuthorizedDefault::All,
+                UsbAuthorizedDefault::Internal,
+                UsbAuthorizedDefault::None,
+            ] {
+                cb_box.append(Some(&policy.to_string()), policy.get_description());
+            }
+            cb_box.set_active_id(Some(&authorized_default.to_string()));
+            cb_box.set_tooltip_text(Some("Devices authorized by default on this bus"));
+            cb_box.set_valign(gtk::Align::Center);
+            cb_box.connect_changed(clone!(@strong app.sender as sender, @strong id => move |cb| {
+                if let Some(policy) = cb.get_active_id() {
+                    activate!(sender, Action::SetUsbAuthorizedDefault(id.clone(), policy.to_string()));
+                }
+            }));
+            main_box.add(&cb_box);
+        }
 
-        text_box.add(&label_main);
-        desc_box.add(&label_type);
+        let button = gtk::Switch::new();
+        button.set_sensitive(device.authorized().is_some());
+        button.set_active(device.authorized().unwrap_or(true));
+        let needs_confirmation = device.is_input_or_storage()
+            || app
+                .state
+                .borrow()
+                .usb_devices
+                .iter()
+                .any(|d| device.is_upstream_of(d) && d.is_input_or_storage());
+        button.connect_state_set(clone!(@strong app.sender as sender => move |switch, on| {
+            // putting the switch back after a cancelled confirmation isn't a change
+            if switch.get_state() == on {
+                return glib::signal::Inhibit(false);
+            }
+
+            if !on && needs_confirmation {
+                activate!(sender, Action::ConfirmUsbDeauthorize(switch.clone(), id.clone()));
+                glib::signal::Inhibit(true)
+            } else {
+                activate!(sender, Action::SetUsbAuthorized(id.clone(), on));
+                glib::signal::Inhibit(false)
+            }
+        }
+        ));
+        button.set_valign(gtk::Align::Center);
+        main_box.add(&button);
+
+        row.add(&main_box);
+        row
+    }
+
+    fn confirm_usb_deauthorize(&self, switch: &gtk::Switch, id: String) {
+        let inner = GpInnerApplication::from_instance(self);
+
+        let (name, is_input_or_storage) = inner
+            .state
+            .borrow()
+            .usb_devices
+            .iter()
+            .find(|d| d.get_id() == id)
+            .map(|d| (d.get_name(), d.is_input_or_storage()))
+            .unwrap_or_default();
+        let text = if is_input_or_storage {
+            "This is an input or storage device. Once the changes are applied, it will stop \
+            working until it is authorized again: you may lose your only keyboard or mouse, \
+            or data on a mounted filesystem."
+        } else {
+            "Input or storage devices are connected through this hub. Once the changes are \
+            applied, they will stop working until it is authorized again: you may lose your \
+            only keyboard or mouse, or data on a mounted filesystem."
+        };
+        self.confirm_switch_off(
+            switch,
+            &format!("Deauthorize {}?", name),
+            text,
+            "_Deauthorize",
+            Action::SetUsbAuthorized(id, false),
+        );
+    }
+
+    fn confirm_usb_persist_off(&self, switch: &gtk::Switch, id: String) {
+        let inner = GpInnerApplication::from_instance(self);
+
+        let (name, mount_points) = inner
+            .state
+            .borrow()
+            .usb_devices
+            .iter()
+            .find(|d| d.get_id() == id)
+            .map(|d| (d.get_name(), d.mounted_filesystems()))
+            .unwrap_or_default();
+
+        if mount_points.is_empty() {
+            switch.set_state(false);
+            activate!(inner.sender, Action::SetUsbPersist(id, false));
+            return;
+        }
+
+        self.confirm_switch_off(
+            switch,
+            &format!("Turn off persist for {}?", name),
+            &format!(
+                "Filesystems are mounted from this device ({}). Without persist, the device \
+                is disconnected if it loses power during suspend, which can corrupt those \
+                filesystems.",
+                mount_points.join(", ")
+            ),
+            "_Turn Off",
+            Action::SetUsbPersist(id, false),
+        );
+    }
+
+    fn confirm_disk_autosuspend(&self, switch: &gtk::Switch, id: String) {
+        let inner = GpInnerApplication::from_instance(self);
+
+        let (name, mount_points) = inner
+            .state
+            .borrow()
+            .disks
+            .iter()
+            .find(|d| d.get_id() == id)
+            .map(|d| (d.get_name(), d.mounted_filesystems()))
+            .unwrap_or_default();
+
+        if mount_points.is_empty() {
+            return;
+        }
+
+        let text = if mount_points.iter().any(|m| m == "/") {
+            "This disk holds the root filesystem: while it is suspended, anything reading or \
+            writing files stalls until it wakes up, which can take seconds."
+                .to_owned()
+        } else {
+            format!(
+                "Filesystems are mounted from this disk ({}). While it is suspended, anything \
+                using them stalls until it wakes up, which can take seconds.",
+                mount_points.join(", ")
+            )
+        };
+        let dialog =
+            self.make_confirm_dialog(&format!("Let {} autosuspend?", name), &text, "_Autosuspend");
+
+        dialog.connect_response(clone!(@strong switch => move |dialog, response| {
+            // the change is already made, only a refusal has to undo it
+            if response != gtk::ResponseType::Accept {
+                switch.set_active(false);
+            }
+            unsafe {
+                dialog.destroy();
+            }
+        }));
+        dialog.show_all();
+    }
+
+    /// A dialog asking for confirmation, the accept button being destructive.
+    fn make_confirm_dialog(
+        &self,
+        title: &str,
+        text: &str,
+        accept_label: &str,
+    ) -> gtk::MessageDialog {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(win, gtk::ApplicationWindow, @inner);
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&win),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Warning,
+            gtk::ButtonsType::None,
+            title,
+        );
+        dialog.set_property_secondary_text(Some(text));
+        dialog.add_button("_Cancel", gtk::ResponseType::Cancel);
+        let button = dialog.add_button(accept_label, gtk::ResponseType::Accept);
+        button.get_style_context().add_class("destructive-action");
+        dialog.set_default_response(gtk::ResponseType::Cancel);
+
+        dialog
+    }
+
+    /// Asks for confirmation before turning a switch off, and only then runs the action.
+    fn confirm_switch_off(
+        &self,
+        switch: &gtk::Switch,
+        title: &str,
+        text: &str,
+        accept_label: &str,
+        action: Action,
+    ) {
+        let inner = GpInnerApplication::from_instance(self);
+        let dialog = self.make_confirm_dialog(title, text, accept_label);
+
+        dialog.connect_response(
+            clone!(@strong inner.sender as sender, @strong switch => move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    switch.set_state(false);
+                    activate!(sender, action.clone());
+                } else {
+                    switch.set_active(true);
+                }
+                unsafe {
+                    dialog.destroy();
+                }
+            }),
+        );
+        dialog.show_all();
+    }
+
+    fn build_profile_entry(&self, name: &str, app: &GpInnerApplication) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let label = gtk::Label::new(Some(name));
+        label.set_halign(gtk::Align::Start);
+        main_box.pack_start(&label, true, true, 0);
+
+        let name = name.to_owned();
+        let load_button = gtk::Button::with_mnemonic("_Load");
+        load_button.set_tooltip_text(Some("Change the settings to the ones in this profile"));
+        load_button.set_valign(gtk::Align::Center);
+        load_button.connect_clicked(
+            clone!(@strong app.sender as sender, @strong name => move |_| {
+                activate!(sender, Action::LoadProfile(name.clone()));
+            }),
+        );
+        main_box.add(&load_button);
+
+        let delete_button =
+            gtk::Button::from_icon_name(Some("user-trash-symbolic"), gtk::IconSize::Button);
+        delete_button.set_tooltip_text(Some("Delete this profile"));
+        delete_button.set_valign(gtk::Align::Center);
+        delete_button.connect_clicked(clone!(@strong app.sender as sender => move |_| {
+            activate!(sender, Action::DeleteProfile(name.clone()));
+        }));
+        main_box.add(&delete_button);
+
+        row.add(&main_box);
+        row
+    }
+
+    fn fill_profile_list(&self) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(main_profile_list_box, gtk::ListBox, @inner);
+        clear_list(&main_profile_list_box);
+
+        match profile::list_profiles() {
+            Ok(names) => {
+                for name in &names {
+                    main_profile_list_box.add(&self.build_profile_entry(name, inner));
+                }
+                self.fill_auto_switch_combos(&names);
+            }
+            Err(e) => error!("failed to list profiles: {}", e),
+        }
+        main_profile_list_box.show_all();
+    }
+
+    /// Lists the profiles in the automatic switching combos, keeping the
+    /// chosen ones if they still exist.
+    fn fill_auto_switch_combos(&self, names: &[String]) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(auto_switch_ac_combo, gtk::ComboBoxText, @inner);
+        get_widget!(auto_switch_battery_combo, gtk::ComboBoxText, @inner);
+
+        let auto_switch = inner.state.borrow().auto_switch.clone();
+        let combos = [
+            (auto_switch_ac_combo, auto_switch.on_ac),
+            (auto_switch_battery_combo, auto_switch.on_battery),
+        ];
+        for (combo, active) in combos.iter() {
+            combo.remove_all();
+            combo.append(Some(NO_PROFILE), "None");
+            for name in names {
+                combo.append(Some(name), name);
+            }
+
+            let active = active.as_ref().filter(|a| names.contains(a));
+            combo.set_active_id(Some(active.map_or(NO_PROFILE, |a| a.as_str())));
+        }
+    }
+
+    /// Lists the drivers of the PCI devices in the driver filter, keeping the
+    /// current choice if that driver is still there.
+    fn fill_pci_driver_filter(&self) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(pci_driver_filter_combo, gtk::ComboBoxText, @inner);
+
+        let active = pci_driver_filter_combo.get_active_id();
+        let mut drivers: Vec<String> = inner
+            .state
+            .borrow()
+            .pci_devices
+            .iter()
+            .filter_map(|d| d.driver().map(|d| d.to_owned()))
+            .collect();
+        drivers.sort();
+        drivers.dedup();
+
+        pci_driver_filter_combo.remove_all();
+        pci_driver_filter_combo.append(Some(ALL_DRIVERS), "All drivers");
+        pci_driver_filter_combo.append(Some(NO_DRIVER), "No driver");
+        for d in &drivers {
+            pci_driver_filter_combo.append(Some(d), d);
+        }
+
+        let active = active
+            .map(|a| a.to_string())
+            .filter(|a| a == NO_DRIVER || drivers.contains(a));
+        pci_driver_filter_combo.set_active_id(Some(active.as_deref().unwrap_or(ALL_DRIVERS)));
+    }
+
+    /// Rebuilds every device list from the current state.
+    fn rebuild_lists(&self) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(main_usb_list_box, gtk::ListBox, @inner);
+        get_widget!(main_usb_wakeup_list_box, gtk::ListBox, @inner);
+        get_widget!(main_usb_auth_list_box, gtk::ListBox, @inner);
+        get_widget!(main_pci_list_box, gtk::ListBox, @inner);
+        get_widget!(main_aspm_list_box, gtk::ListBox, @inner);
+        get_widget!(main_sata_list_box, gtk::ListBox, @inner);
+        get_widget!(main_disks_list_box, gtk::ListBox, @inner);
+        get_widget!(main_audio_list_box, gtk::ListBox, @inner);
+        get_widget!(main_battery_list_box, gtk::ListBox, @inner);
+        get_widget!(main_wakeup_list_box, gtk::ListBox, @inner);
+        get_widget!(main_acpi_wakeup_list_box, gtk::ListBox, @inner);
+
+        clear_list(&main_usb_list_box);
+        clear_list(&main_usb_wakeup_list_box);
+        clear_list(&main_usb_auth_list_box);
+        clear_list(&main_pci_list_box);
+        clear_list(&main_aspm_list_box);
+        clear_list(&main_sata_list_box);
+        clear_list(&main_disks_list_box);
+        clear_list(&main_audio_list_box);
+        clear_list(&main_battery_list_box);
+        clear_list(&main_wakeup_list_box);
+        clear_list(&main_acpi_wakeup_list_box);
+
+        // the widgets flagged in error are gone
+        inner.state.borrow_mut().errors = 0;
+
+        self.fill_list();
+        main_usb_list_box.show_all();
+        main_usb_wakeup_list_box.show_all();
+        main_usb_auth_list_box.show_all();
+        main_pci_list_box.show_all();
+        main_aspm_list_box.show_all();
+        main_sata_list_box.show_all();
+        main_disks_list_box.show_all();
+        main_audio_list_box.show_all();
+        main_battery_list_box.show_all();
+        main_wakeup_list_box.show_all();
+        main_acpi_wakeup_list_box.show_all();
+
+        self.fill_pci_driver_filter();
+        inner.fill_power_supply_box();
+
+        get_widget!(aspm_policy_combo, gtk::ComboBoxText, @inner);
+        if let Some(policy) = &inner.state.borrow().aspm_policy {
+            aspm_policy_combo.set_active_id(Some(policy.current()));
+        }
+
+        get_widget!(platform_profile_combo, gtk::ComboBoxText, @inner);
+        if let Some(platform_profile) = &inner.state.borrow().platform_profile {
+            platform_profile_combo.set_active_id(Some(platform_profile.current()));
+        }
+
+        get_widget!(mem_sleep_combo, gtk::ComboBoxText, @inner);
+        if let Some(mem_sleep) = &inner.state.borrow().mem_sleep {
+            mem_sleep_combo.set_active_id(Some(mem_sleep.current()));
+        }
+    }
+
+    fn make_pci_description(&self, device: &PciDevice) -> gtk::Box {
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let desc_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let label_main = gtk::Label::new(Some(&device.get_name()));
+        let label_type = gtk::Label::new(Some(&device.get_kind_description()));
+        let label_info = gtk::Label::new(Some(&device.get_description()));
+        let label_driver = gtk::Label::new(Some(&device.get_driver_description()));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+        label_type.get_style_context().add_class("type_label");
+        label_type.get_style_context().add_class("desc_label");
+        label_type.get_style_context().add_class("dim-label");
+        label_driver.get_style_context().add_class("desc_label");
+        label_driver.get_style_context().add_class("dim-label");
+        label_driver.set_tooltip_text(Some("Kernel driver (and module)"));
+
+        text_box.add(&label_main);
+        desc_box.add(&label_type);
         desc_box.add(&label_info);
+        desc_box.add(&label_driver);
         text_box.add(&desc_box);
         text_box.set_valign(gtk::Align::Center);
         text_box.set_halign(gtk::Align::Start);
         label_info.set_halign(gtk::Align::Start);
         label_main.set_halign(gtk::Align::Start);
+
+        text_box
+    }
+
+    fn build_aspm_entry(
+        &self,
+        device: &PciDevice,
+        link: &pci::PciLink,
+        app: &GpInnerApplication,
+    ) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        main_box.pack_start(&self.make_pci_description(device), true, true, 0);
+
+        for (state, enabled) in link.states() {
+            let button = gtk::CheckButton::with_label(state.get_name());
+            button.set_active(*enabled);
+            button.set_valign(gtk::Align::Center);
+            let id = device.get_id().to_owned();
+            let state = *state;
+            button.connect_toggled(clone!(@strong app.sender as sender => move |b| {
+                activate!(sender, Action::SetPciLinkState(id.clone(), state, b.get_active()));
+            }));
+            main_box.add(&button);
+        }
+
+        row.add(&main_box);
+        row
+    }
+
+    fn build_sata_entry(
+        &self,
+        host: &SataHost,
+        controller: Option<&PciDevice>,
+        app: &GpInnerApplication,
+    ) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let label_main = gtk::Label::new(Some(host.get_id()));
+        let controller_name = match controller {
+            Some(c) => c.get_name(),
+            None => "Unknown controller".to_owned(),
+        };
+        let label_info = gtk::Label::new(Some(&controller_name));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+        if let Some(slot) = host.pci_slot() {
+            label_info.set_tooltip_text(Some(slot));
+        }
+
+        text_box.add(&label_main);
+        text_box.add(&label_info);
+        text_box.set_valign(gtk::Align::Center);
+        text_box.set_halign(gtk::Align::Start);
+        label_info.set_halign(gtk::Align::Start);
+        label_main.set_halign(gtk::Align::Start);
         main_box.pack_start(&text_box, true, true, 0);
-        let cb_box = gtk::ComboBoxText::with_entry();
-        let button = gtk::Switch::new();
-        button.set_active(device.can_autosuspend());
-        let id = device.get_id().to_owned();
-        button.connect_state_set(
-            clone!(@strong app.sender as sender, @strong cb_box as cb, @strong self as app, @strong id => move |_, on| {
-                activate!(sender, Action::SetPciAutoSuspend(id.clone(), on));
-                if on {
-                    activate!(sender, Action::SetPciAutoSuspendDelay(cb.clone(),
-                    id.clone(),
-                    cb.get_active_text().map(|s| s.as_str().to_owned()).unwrap_or_else(String::new),
+
+        let cb_box = gtk::ComboBoxText::new();
+        cb_box.set_valign(gtk::Align::Center);
+        for policy in sata::POLICIES.iter() {
+            cb_box.append(Some(policy), SataHost::get_policy_description(policy));
+        }
+        cb_box.set_active_id(Some(host.policy()));
+        let id = host.get_id().to_owned();
+        cb_box.connect_changed(clone!(@strong app.sender as sender => move |cb| {
+            if let Some(policy) = cb.get_active_id() {
+                activate!(sender, Action::SetSataPolicy(id.clone(), policy.to_string()));
+            }
+        }));
+        main_box.add(&cb_box);
+
+        row.add(&main_box);
+        row
+    }
+
+    /// Fills the CPU list, with a first line changing all the policies at once.
+    fn fill_cpu_list(&self) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(main_cpu_list_box, gtk::ListBox, @inner);
+        clear_list(&main_cpu_list_box);
+
+        let mut entries = Vec::new();
+        {
+            let state = inner.state.borrow();
+            let policies = &state.cpu_policies;
+            if policies.len() > 1 {
+                let common = |values: &dyn Fn(&CpuPolicy) -> &[String]| -> Vec<String> {
+                    values(&policies[0])
+                        .iter()
+                        .filter(|v| policies.iter().all(|p| values(p).contains(v)))
+                        .cloned()
+                        .collect()
+                };
+                let governors = common(&|p| p.available_governors());
+                let epps = common(&|p| p.available_epp());
+                let governor = Some(policies[0].governor())
+                    .filter(|g| policies.iter().all(|p| p.governor() == *g));
+                let epp = policies[0]
+                    .epp()
+                    .filter(|e| policies.iter().all(|p| p.epp() == Some(*e)));
+
+                entries.push(self.build_cpu_entry(
+                    "All CPUs",
+                    &format!("{} policies", policies.len()),
+                    (&governors, governor),
+                    (&epps, epp),
+                    None,
+                    inner,
                 ));
-                } else {
-                    app.set_error(&cb, None);
+            }
+            for p in policies {
+                let desc = match p.driver() {
+                    Some(driver) => format!("CPUs {}, {}", p.cpus(), driver),
+                    None => format!("CPUs {}", p.cpus()),
+                };
+                entries.push(self.build_cpu_entry(
+                    p.get_id(),
+                    &desc,
+                    (p.available_governors(), Some(p.governor())),
+                    (p.available_epp(), p.epp()),
+                    Some(p.get_id().to_owned()),
+                    inner,
+                ));
+            }
+        }
+        for e in entries {
+            main_cpu_list_box.add(&e);
+        }
+        main_cpu_list_box.show_all();
+    }
+
+    /// Builds a line with a governor and a preference combo, for one policy if
+    /// there is an `id`, or for all of them.
+    fn build_cpu_entry(
+        &self,
+        title: &str,
+        desc: &str,
+        governors: (&[String], Option<&str>),
+        epps: (&[String], Option<&str>),
+        id: Option<String>,
+        app: &GpInnerApplication,
+    ) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let label_main = gtk::Label::new(Some(title));
+        let label_info = gtk::Label::new(Some(desc));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+
+        text_box.add(&label_main);
+        text_box.add(&label_info);
+        text_box.set_valign(gtk::Align::Center);
+        text_box.set_halign(gtk::Align::Start);
+        label_info.set_halign(gtk::Align::Start);
+        label_main.set_halign(gtk::Align::Start);
+        main_box.pack_start(&text_box, true, true, 0);
+
+        let cb_governor = gtk::ComboBoxText::new();
+        cb_governor.set_valign(gtk::Align::Center);
+        cb_governor.set_tooltip_text(Some("Governor"));
+        for g in governors.0 {
+            cb_governor.append(Some(g), g);
+        }
+        cb_governor.set_active_id(governors.1);
+        cb_governor.connect_changed(
+            clone!(@strong app.sender as sender, @strong id => move |cb| {
+                if let Some(governor) = cb.get_active_id() {
+                    activate!(sender, Action::SetCpuGovernor(id.clone(), governor.to_string()));
                 }
-                glib::signal::Inhibit(false)
+            }),
+        );
+        main_box.add(&cb_governor);
+
+        if !epps.0.is_empty() {
+            let cb_epp = gtk::ComboBoxText::new();
+            cb_epp.set_valign(gtk::Align::Center);
+            cb_epp.set_tooltip_text(Some("Energy-performance preference"));
+            for e in epps.0 {
+                cb_epp.append(Some(e), &e.replace('_', " "));
             }
+            cb_epp.set_active_id(epps.1);
+            cb_epp.connect_changed(clone!(@strong app.sender as sender => move |cb| {
+                if let Some(epp) = cb.get_active_id() {
+                    activate!(sender, Action::SetCpuEpp(id.clone(), epp.to_string()));
+                }
+            }));
+            main_box.add(&cb_epp);
+        }
+
+        row.add(&main_box);
+        row
+    }
+
+    fn build_battery_entry(&self, battery: &Battery, app: &GpInnerApplication) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let label_main = gtk::Label::new(Some(&battery.get_name()));
+        let label_info = gtk::Label::new(Some(&battery.get_description()));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+
+        text_box.add(&label_main);
+        text_box.add(&label_info);
+        text_box.set_valign(gtk::Align::Center);
+        text_box.set_halign(gtk::Align::Start);
+        label_info.set_halign(gtk::Align::Start);
+        label_main.set_halign(gtk::Align::Start);
+        main_box.pack_start(&text_box, true, true, 0);
+
+        if battery.start_threshold().is_none() && battery.end_threshold().is_none() {
+            main_box.add(&make_unsupported_label());
+            row.add(&main_box);
+            return row;
+        }
+
+        let make_spin = |name: &str, value: Option<u8>| {
+            value.map(|value| {
+                let label = gtk::Label::new(Some(name));
+                label.get_style_context().add_class("dim-label");
+                label.set_valign(gtk::Align::Center);
+                main_box.add(&label);
+                let spin = gtk::SpinButton::with_range(0.0, 100.0, 1.0);
+                spin.set_value(value as f64);
+                spin.set_valign(gtk::Align::Center);
+                main_box.add(&spin);
+                spin
+            })
+        };
+        let start = make_spin("Start at", battery.start_threshold());
+        let end = make_spin("Stop at", battery.end_threshold());
+
+        let id = battery.get_id().to_owned();
+        for spin in start.iter().chain(end.iter()) {
+            spin.connect_value_changed(
+                clone!(@strong app.sender as sender, @strong id, @strong start, @strong end => move |_| {
+                    activate!(sender, Action::SetBatteryThresholds(id.clone(), start.clone(), end.clone()));
+                }),
+            );
+        }
+
+        row.add(&main_box);
+        row
+    }
+
+    fn build_wakeup_entry(
+        &self,
+        source: &WakeupSource,
+        app: &GpInnerApplication,
+    ) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let label_main = gtk::Label::new(Some(source.get_name()));
+
+        // the USB or PCI device where wakeup can be disabled
+        let state = app.state.borrow();
+        let (description, link) = match source.device() {
+            Some(d) => match d.subsystem.as_deref() {
+                Some("usb") => match state.usb_devices.iter().find(|u| u.get_id() == d.id) {
+                    Some(u) => (format!("{} ({})", u.get_name(), d.id), Some("usb")),
+                    None => (format!("USB {}", d.id), None),
+                },
+                Some("pci") => match state.pci_devices.iter().find(|p| p.get_id() == d.id) {
+                    Some(p) => (format!("{} ({})", p.get_name(), d.id), Some("pci")),
+                    None => (format!("PCI {}", d.id), None),
+                },
+                Some(subsystem) => (format!("{} {}", subsystem, d.id), None),
+                None => (d.id.clone(), None),
+            },
+            None => ("No device".to_owned(), None),
+        };
+        let label_info = gtk::Label::new(Some(&description));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+
+        text_box.add(&label_main);
+        text_box.add(&label_info);
+        text_box.set_valign(gtk::Align::Center);
+        text_box.set_halign(gtk::Align::Start);
+        label_info.set_halign(gtk::Align::Start);
+        label_main.set_halign(gtk::Align::Start);
+        main_box.pack_start(&text_box, true, true, 0);
+
+        let count_label = gtk::Label::new(Some(&format!(
+            "{} wakeups, {} events",
+            source.wakeup_count(),
+            source.event_count()
+        )));
+        count_label.set_valign(gtk::Align::Center);
+        let seconds = |d: Duration| humantime::format_duration(Duration::from_secs(d.as_secs()));
+        let mut tooltip = format!(
+            "Kept the system awake for {}",
+            seconds(source.active_time())
+        );
+        if let Some(ago) = source.last_change_ago() {
+            tooltip.push_str(&format!(", last active {} ago", seconds(ago)));
+        }
+        count_label.set_tooltip_text(Some(&tooltip));
+        main_box.add(&count_label);
+
+        if let (Some(subsystem), Some(device)) = (link, source.device()) {
+            let button =
+                gtk::Button::from_icon_name(Some("go-next-symbolic"), gtk::IconSize::Button);
+            button.set_relief(gtk::ReliefStyle::None);
+            button.set_valign(gtk::Align::Center);
+            button.set_tooltip_text(Some("Show the device, to disable its wakeup"));
+            let id = device.id.clone();
+            button.connect_clicked(clone!(@strong app.sender as sender => move |_| {
+                activate!(sender, Action::ShowWakeupDevice(subsystem.to_owned(), id.clone()));
+            }));
+            main_box.add(&button);
+        }
+
+        row.add(&main_box);
+        row
+    }
+
+    fn build_acpi_wakeup_entry(
+        &self,
+        device: &AcpiWakeupDevice,
+        app: &GpInnerApplication,
+    ) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let label_main = gtk::Label::new(Some(device.get_name()));
+
+        // name the devices we know about, the others as the table does
+        let state = app.state.borrow();
+        let nodes: Vec<String> = device
+            .nodes()
+            .iter()
+            .map(|n| {
+                let name = match n.bus.as_str() {
+                    "pci" => state
+                        .pci_devices
+                        .iter()
+                        .find(|d| d.get_id() == n.id)
+                        .map(|d| d.get_name()),
+                    "usb" => state
+                        .usb_devices
+                        .iter()
+                        .find(|d| d.get_id() == n.id)
+                        .map(|d| d.get_name()),
+                    _ => None,
+                };
+                match name {
+                    Some(name) => format!("{} ({})", name, n.id),
+                    None => format!("{}:{}", n.bus, n.id),
+                }
+            })
+            .collect();
+        let mut description = format!("Wakes from {}", device.sleep_state());
+        if !nodes.is_empty() {
+            description.push_str(", ");
+            description.push_str(&nodes.join(", "));
+        }
+        let label_info = gtk::Label::new(Some(&description));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+        label_info.set_line_wrap(true);
+
+        text_box.add(&label_main);
+        text_box.add(&label_info);
+        text_box.set_valign(gtk::Align::Center);
+        text_box.set_halign(gtk::Align::Start);
+        label_info.set_halign(gtk::Align::Start);
+        label_main.set_halign(gtk::Align::Start);
+        main_box.pack_start(&text_box, true, true, 0);
+
+        let switch = gtk::Switch::new();
+        switch.set_active(device.enabled());
+        switch.set_valign(gtk::Align::Center);
+        switch.set_tooltip_text(Some("Allow the device to wake the system up"));
+        let name = device.get_name().to_owned();
+        switch.connect_property_active_notify(clone!(@strong app.sender as sender => move |s| {
+            activate!(sender, Action::SetAcpiWakeup(name.clone(), s.get_active()));
+        }));
+        main_box.add(&switch);
+
+        row.add(&main_box);
+        row
+    }
+
+    /// Shows the row of a USB or PCI device, with its details.
+    fn show_wakeup_device(&self, subsystem: &str, id: &str) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(win, gtk::ApplicationWindow, @inner);
+        get_widget!(main_usb_wakeup_list_box, gtk::ListBox, @inner);
+        get_widget!(main_pci_list_box, gtk::ListBox, @inner);
+        get_widget!(usb_wakeup_scroll, gtk::ScrolledWindow, @inner);
+        get_widget!(pci_scroll, gtk::ScrolledWindow, @inner);
+
+        let (action, list_box, scroll) = match subsystem {
+            "usb" => (
+                "show_usb_wakeup",
+                main_usb_wakeup_list_box,
+                usb_wakeup_scroll,
             ),
+            _ => ("show_pci", main_pci_list_box, pci_scroll),
+        };
+        let row = match list_box
+            .get_children()
+            .into_iter()
+            .find(|r| r.get_widget_name() == id)
+        {
+            Some(row) => row,
+            None => return,
+        };
+        if !row.get_child_visible() {
+            get_widget!(pci_driver_filter_combo, gtk::ComboBoxText, @inner);
+            pci_driver_filter_combo.set_active_id(Some(ALL_DRIVERS));
+        }
+        win.activate_action(action, None);
+
+        // the row only has a position once the pane is shown
+        glib::timeout_add_local(
+            100,
+            clone!(@weak row, @weak list_box, @weak scroll => @default-return glib::Continue(false), move || {
+                if let Some((_, y)) = row.translate_coordinates(&list_box, 0, 0) {
+                    let adjustment = scroll.get_vadjustment();
+                    if let Some(adjustment) = adjustment {
+                        adjustment.clamp_page(y as f64, (y + row.get_allocated_height()) as f64);
+                    }
+                }
+                row.activate();
+                glib::Continue(false)
+            }),
         );
-        button.set_valign(gtk::Align::Center);
-        main_box.add(&button);
-        cb_box.set_valign(gtk::Align::Center);
-        cb_box.append_text("0 seconds");
-        let delay = device.delay();
-        let autosuspend = device.can_autosuspend();
-        cb_box.set_sensitive(autosuspend);
+    }
 
-        if autosuspend && delay != 0 {
-            cb_box
-                .append_text(&humantime::format_duration(Duration::from_millis(delay)).to_string());
-            cb_box.set_active(Some(1));
-        } else {
-            cb_box.set_active(Some(0));
+    /// Shows the average draw before and after applying the changes.
+    fn fill_measure_list(&self, before: &Measurement, after: &Measurement) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(main_measure_list_box, gtk::ListBox, @inner);
+        clear_list(&main_measure_list_box);
+
+        let describe = |m: &Measurement| match (m.mean(), m.variance()) {
+            (Some(mean), Some(variance)) => format!(
+                "{:.2} W ± {:.2} W ({} samples)",
+                mean,
+                variance.sqrt(),
+                m.samples().len()
+            ),
+            (Some(mean), None) => format!("{:.2} W (1 sample)", mean),
+            _ => "No sample".to_owned(),
+        };
+        let mut rows = vec![
+            ("Before applying", describe(before)),
+            ("After applying", describe(after)),
+        ];
+        if let (Some(b), Some(a)) = (before.mean(), after.mean()) {
+            // no relative difference from a battery that wasn't drawing anything
+            let difference = if b > 0.0 {
+                format!("{:+.2} W ({:+.1}%)", a - b, (a - b) / b * 100.0)
+            } else {
+                format!("{:+.2} W", a - b)
+            };
+            rows.push(("Difference", difference));
         }
-        cb_box.append_text("1 second");
-        cb_box.append_text("2 seconds");
-        cb_box.append_text("5 seconds");
-        cb_box.append_text("20 seconds");
-        cb_box.append_text("1 minute");
-        cb_box.append_text("5 minutes");
-        cb_box.connect_changed(clone!(@strong app.sender as sender => move |cb| {
-            activate!(sender, Action::SetPciAutoSuspendDelay(cb.clone(),
-                id.clone(),
-                cb.get_active_text().map(|s| s.as_str().to_owned()).unwrap_or_else(String::new),
+
+        for (title, value) in rows {
+            let row = gtk::ListBoxRow::new();
+            row.set_can_focus(false);
+            let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            let title_label = gtk::Label::new(Some(title));
+            title_label.set_halign(gtk::Align::Start);
+            let value_label = gtk::Label::new(Some(&value));
+            value_label.set_selectable(true);
+            main_box.pack_start(&title_label, true, true, 0);
+            main_box.add(&value_label);
+            row.add(&main_box);
+            main_measure_list_box.add(&row);
+        }
+        main_measure_list_box.show_all();
+    }
+
+    fn export_measurement(&self) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(win, gtk::ApplicationWindow, @inner);
+
+        let csv = match &inner.state.borrow().measurement {
+            Some((before, after)) => power_supply::to_csv(before, after),
+            None => return,
+        };
+
+        let dialog = gtk::FileChooserNative::new(
+            Some("Export Samples"),
+            Some(&win),
+            gtk::FileChooserAction::Save,
+            Some("_Export"),
+            Some("_Cancel"),
+        );
+        dialog.set_current_name("power-measurement.csv");
+        dialog.set_do_overwrite_confirmation(true);
+        if dialog.run() == gtk::ResponseType::Accept {
+            if let Some(path) = dialog.get_filename() {
+                match std::fs::write(&path, csv) {
+                    Ok(()) => info!("exported samples to {}", path.display()),
+                    Err(e) => error!("error exporting samples: {}", e),
+                }
+            }
+        }
+    }
+
+    fn build_audio_entries(
+        &self,
+        audio: &AudioPowerSave,
+        app: &GpInnerApplication,
+    ) -> Vec<gtk::ListBoxRow> {
+        let mut rows = Vec::new();
+
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let label = gtk::Label::new(Some("Power down the codec after (seconds)"));
+        label.set_halign(gtk::Align::Start);
+        main_box.pack_start(&label, true, true, 0);
+        let spin = gtk::SpinButton::with_range(-1.0, 3600.0, 1.0);
+        spin.set_value(audio.power_save() as f64);
+        spin.set_valign(gtk::Align::Center);
+        spin.set_tooltip_text(Some("0 and -1 keep the codec always powered"));
+        spin.connect_value_changed(clone!(@strong app.sender as sender => move |spin| {
+            activate!(sender, Action::SetAudioPowerSave(spin.get_value_as_int()));
+        }));
+        main_box.add(&spin);
+        row.add(&main_box);
+        rows.push(row);
+
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let label = gtk::Label::new(Some("Power down the controller too"));
+        label.set_halign(gtk::Align::Start);
+        main_box.pack_start(&label, true, true, 0);
+        match audio.power_save_controller() {
+            Some(enabled) => {
+                let button = gtk::Switch::new();
+                button.set_active(enabled);
+                button.set_valign(gtk::Align::Center);
+                button.connect_state_set(clone!(@strong app.sender as sender => move |_, on| {
+                    activate!(sender, Action::SetAudioPowerSaveController(on));
+                    glib::signal::Inhibit(false)
+                }));
+                main_box.add(&button);
+            }
+            None => main_box.add(&make_unsupported_label()),
+        }
+        row.add(&main_box);
+        rows.push(row);
+
+        for card in audio.cards() {
+            let row = gtk::ListBoxRow::new();
+            row.set_can_focus(false);
+            let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+            let label_main = gtk::Label::new(Some(&card.name));
+            let label_info = gtk::Label::new(Some(&format!(
+                "card {}: {} ({})",
+                card.index, card.id, card.driver
+            )));
+            label_info.get_style_context().add_class("desc_label");
+            label_info.get_style_context().add_class("dim-label");
+            label_main.set_halign(gtk::Align::Start);
+            label_info.set_halign(gtk::Align::Start);
+            text_box.add(&label_main);
+            text_box.add(&label_info);
+            main_box.pack_start(&text_box, true, true, 0);
+
+            let affected = if card.is_hda() {
+                "Affected"
+            } else {
+                "Not affected"
+            };
+            let label = gtk::Label::new(Some(affected));
+            label.get_style_context().add_class("dim-label");
+            label.set_valign(gtk::Align::Center);
+            main_box.add(&label);
+            row.add(&main_box);
+            rows.push(row);
+        }
+
+        rows
+    }
+
+    /// Shows what is going to be written to a configuration file, and writes
+    /// it if the user agrees.
+    fn confirm_write_file(&self, title: &str, path: &str, content: String) {
+        let inner = GpInnerApplication::from_instance(self);
+        get_widget!(win, gtk::ApplicationWindow, @inner);
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&win),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            title,
+        );
+        dialog.set_property_secondary_text(Some(&format!(
+            "The following will be written to {}, replacing it if it exists:",
+            path
+        )));
+        let text_view = gtk::TextView::new();
+        text_view.set_editable(false);
+        text_view.set_monospace(true);
+        text_view.set_left_margin(6);
+        text_view.set_right_margin(6);
+        if let Some(buffer) = text_view.get_buffer() {
+            buffer.set_text(&content);
+        }
+        if let Some(area) = dialog
+            .get_message_area()
+            .and_then(|w| w.downcast::<gtk::Box>().ok())
+        {
+            area.add(&text_view);
+        }
+        dialog.add_button("_Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("_Write", gtk::ResponseType::Accept);
+        dialog.set_default_response(gtk::ResponseType::Cancel);
+
+        let path = path.to_owned();
+        dialog.connect_response(
+            clone!(@strong inner.sender as sender => move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    // the dialog stays until the file is written, to show the errors
+                    dialog.set_sensitive(false);
+                    activate!(sender, Action::WriteFile(dialog.clone(), path.clone(), content.clone()));
+                } else {
+                    unsafe {
+                        dialog.destroy();
+                    }
+                }
+            }),
+        );
+        dialog.show_all();
+    }
+
+    fn build_disk_entry(&self, disk: &Disk, app: &GpInnerApplication) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        let text_box = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        let label_main = gtk::Label::new(Some(&disk.get_name()));
+        let label_info = gtk::Label::new(Some(&disk.get_description()));
+
+        label_info.get_style_context().add_class("desc_label");
+        label_info.get_style_context().add_class("dim-label");
+        label_info.set_tooltip_text(Some(disk.get_id()));
+
+        text_box.add(&label_main);
+        text_box.add(&label_info);
+        text_box.set_valign(gtk::Align::Center);
+        text_box.set_halign(gtk::Align::Start);
+        label_info.set_halign(gtk::Align::Start);
+        label_main.set_halign(gtk::Align::Start);
+        main_box.pack_start(&text_box, true, true, 0);
+
+        let mounted = disk.mounted_filesystems();
+        if mounted.iter().any(|m| m == "/") {
+            main_box.add(&self.make_warning_icon(
+                "This disk holds the root filesystem: suspending it will stall the whole system \
+                 until it wakes up.",
             ));
+        } else if !mounted.is_empty() {
+            main_box.add(&self.make_warning_icon(&format!(
+                "Filesystems mounted from this disk: {}",
+                mounted.join(", ")
+            )));
+        }
+
+        let id = disk.get_id().to_owned();
+        let button = self.add_autosuspend_controls(
+            &main_box,
+            disk.can_autosuspend(),
+            disk.delay(),
+            app,
+            clone!(@strong id => move |on| Action::SetDiskAutoSuspend(id.clone(), on)),
+            clone!(@strong id => move |cb, delay| Action::SetDiskAutoSuspendDelay(cb, id.clone(), delay)),
+        );
+        // runs after the change is made, which is undone if it isn't confirmed
+        button.connect_state_set(clone!(@strong app.sender as sender => move |switch, on| {
+            if on && !switch.get_state() {
+                activate!(sender, Action::ConfirmDiskAutoSuspend(switch.clone(), id.clone()));
+            }
+            glib::signal::Inhibit(false)
         }));
-        main_box.add(&cb_box);
-        button
-            .bind_property("active", &cb_box, "sensitive")
-            .flags(
-                glib::BindingFlags::DEFAULT
-                    | glib::BindingFlags::SYNC_CREATE
-                    | glib::BindingFlags::BIDIRECTIONAL,
-            )
-            .build();
+        row.add(&main_box);
+        row
+    }
+
+    /// Makes a label showing the power state of a PCI device, kept up to date
+    /// for as long as the label exists.
+    fn make_power_state_label(
+        &self,
+        state: &str,
+        id: &str,
+        app: &GpInnerApplication,
+    ) -> gtk::Label {
+        let label = gtk::Label::new(Some(state));
+        label.get_style_context().add_class("dim-label");
+        label.set_valign(gtk::Align::Center);
+        label.set_tooltip_text(Some("Current power state"));
+
+        let id = id.to_owned();
+        glib::timeout_add_seconds_local(
+            2,
+            clone!(@weak label, @strong app.state as state => @default-return glib::Continue(false), move || {
+                let power_state = state
+                    .borrow()
+                    .pci_devices
+                    .iter()
+                    .find(|d| d.get_id() == id)
+                    .and_then(|d| d.power_state());
+                match power_state {
+                    Some(s) => {
+                        label.set_text(&s);
+                        glib::Continue(true)
+                    }
+                    None => glib::Continue(false),
+                }
+            }),
+        );
+
+        label
+    }
+
+    fn build_pci_entry(&self, device: &PciDevice, app: &GpInnerApplication) -> gtk::ListBoxRow {
+        let row = gtk::ListBoxRow::new();
+        row.set_can_focus(false);
+        row.set_activatable(true);
+        row.set_widget_name(device.get_id());
+        let main_box = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        main_box.pack_start(&self.make_pci_description(device), true, true, 0);
+        let id = device.get_id().to_owned();
+        if let Some(state) = device.power_state() {
+            main_box.add(&self.make_power_state_label(&state, &id, app));
+        }
+        if let Some(allowed) = device.d3cold_allowed() {
+            let d3cold_button = gtk::CheckButton::with_label("D3cold");
+            d3cold_button.set_active(allowed);
+            d3cold_button.set_valign(gtk::Align::Center);
+            d3cold_button.set_tooltip_text(Some(
+                "Allow the device to be completely powered off when suspended",
+            ));
+            d3cold_button.connect_toggled(
+                clone!(@strong app.sender as sender, @strong id => move |b| {
+                    activate!(sender, Action::SetPciD3coldAllowed(id.clone(), b.get_active()));
+                }),
+            );
+            main_box.add(&d3cold_button);
+        }
+        if let Some(allowed) = device.allow_wakeup() {
+            let wakeup_button = gtk::CheckButton::with_label("Wakeup");
+            wakeup_button.set_active(allowed);
+            wakeup_button.set_valign(gtk::Align::Center);
+            wakeup_button.set_tooltip_text(Some("Allow the device to wake the system up"));
+            wakeup_button.connect_toggled(
+                clone!(@strong app.sender as sender, @strong id => move |b| {
+                    activate!(sender, Action::SetPciAllowWakeup(id.clone(), b.get_active()));
+                }),
+            );
+            main_box.add(&wakeup_button);
+        }
+        if let Some(warning) = device.link_warning() {
+            main_box.add(&self.make_warning_icon(&warning));
+        }
+        if let Some(reason) = device.autosuspend_unsupported_reason() {
+            main_box.add(&self.make_warning_icon(&reason));
+        }
+        self.add_autosuspend_controls(
+            &main_box,
+            device.can_autosuspend(),
+            device.delay(),
+            app,
+            clone!(@strong id => move |on| Action::SetPciAutoSuspend(id.clone(), on)),
+            move |cb, delay| Action::SetPciAutoSuspendDelay(cb, id.clone(), delay),
+        );
         row.add(&main_box);
         row
     }
 
     fn set_error(&self, cb: &gtk::ComboBoxText, error: Option<&str>) {
-        debug!("setting error state to '{}'", error.is_some());
+        // the entry of the combo is what shows the error
+        self.set_widget_error(&cb.get_child().unwrap(), error.is_some());
+        cb.set_tooltip_text(error);
+    }
+
+    /// Flags a widget as holding an invalid value, which keeps the changes
+    /// from being applied until it is fixed.
+    fn set_widget_error(&self, widget: &gtk::Widget, error: bool) {
+        debug!("setting error state to '{}'", error);
 
         let inner = GpInnerApplication::from_instance(self);
-        let context = cb.get_child().unwrap().get_style_context();
+        let context = widget.get_style_context();
 
-        if !context.has_class("error") && error.is_some() {
+        if !context.has_class("error") && error {
             context.add_class("error");
 
             inner.state.borrow_mut().errors += 1;
-        } else if context.has_class("error") && error.is_none() {
+        } else if context.has_class("error") && !error {
             context.remove_class("error");
 
             inner.state.borrow_mut().errors -= 1;
         }
-        cb.set_tooltip_text(error);
     }
 
     fn process_action(&self, action: Action) -> glib::Continue {
@@ -672,74 +3034,41 @@ impl GPApplication {
 
         match action {
             Action::ApplyChanges => {
+                if inner.state.borrow().applying {
+                    info!("already applying changes, ignoring");
+                    return glib::Continue(true);
+                }
+                inner.state.borrow_mut().applying = true;
+
                 glib::MainContext::default().spawn_local({
                     let state = inner.state.clone();
                     let sender = inner.sender.clone();
                     async move {
-                        match apply_changes(state).await {
+                        match apply_changes(state.clone()).await {
                             Ok(()) => {
                                 info!("successfully applied changes");
-                                activate!(sender, Action::ResetChanged);
+                                // the next changes are compared to the values read again
+                                activate!(sender, Action::Refresh);
                             }
                             Err(e) => error!("error applying changes: {}", e),
                         }
+
+                        let pending = {
+                            let mut state = state.borrow_mut();
+                            state.applying = false;
+                            state.pending_power_source.take()
+                        };
+                        if let Some(on_ac) = pending {
+                            activate!(sender, Action::PowerSourceChanged(on_ac));
+                        }
                     }
-                });
-            }
-            Action::ResetChanged => inner.reset_changed(),
-            Action::Refresh => {
-                get_widget!(
-                    main_usb_list_box,
-                    gtk::ListBox,
-                    @inner
-                );
-                main_usb_list_box.foreach(clone!(@weak main_usb_list_box => move |item| {
-                    main_usb_list_box.remove(item);
-                }));
-                get_widget!(
-                    main_usb_wakeup_list_box,
-                    gtk::ListBox,
-                    @inner
-                );
-                main_usb_wakeup_list_box.foreach(
-                    clone!(@weak main_usb_wakeup_list_box => move |item| {
-                        main_usb_wakeup_list_box.remove(item);
-                    }),
-                );
-                let devices = match usb::list_devices() {
-                    Ok(d) => d,
-                    Err(e) => {
-                        error!("failed to load devices: {}", e);
-                        Vec::new()
-                    }
-                };
-                inner.state.borrow_mut().usb_devices = devices;
-
-                get_widget!(
-                    main_pci_list_box,
-                    gtk::ListBox,
-                    @inner
-                );
-                main_pci_list_box.foreach(clone!(@weak main_pci_list_box => move |item| {
-                    main_pci_list_box.remove(item);
-                }));
-                let devices = match pci::list_devices() {
-                    Ok(d) => d,
-                    Err(e) => {
-                        error!("failed to load devices: {}", e);
-                        Vec::new()
-                    }
-                };
-                inner.state.borrow_mut().pci_devices = devices;
+                });
+            }
+            Action::Refresh => {
+                inner.state.borrow_mut().reload();
 
-                self.fill_list(
-                    &main_usb_list_box,
-                    &main_usb_wakeup_list_box,
-                    &main_pci_list_box,
-                );
-                main_usb_list_box.show_all();
-                main_usb_wakeup_list_box.show_all();
-                main_pci_list_box.show_all();
+                self.rebuild_lists();
+                self.fill_profile_list();
 
                 inner.reset_changed();
             }
@@ -781,6 +3110,84 @@ impl GPApplication {
 
                 inner.set_changed();
             }
+            Action::SetUsbAuthorized(id, authorized) => {
+                for d in inner.state.borrow_mut().usb_devices.iter_mut() {
+                    if d.get_id() == id {
+                        if let Err(e) = d.set_authorized(authorized) {
+                            warn!("ignoring error: {}", e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetUsbHardwareLpm(id, enabled) => {
+                for d in inner.state.borrow_mut().usb_devices.iter_mut() {
+                    if d.get_id() == id {
+                        if let Err(e) = d.set_usb2_hardware_lpm(enabled) {
+                            warn!("ignoring error: {}", e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetUsbPersist(id, persist) => {
+                for d in inner.state.borrow_mut().usb_devices.iter_mut() {
+                    if d.get_id() == id {
+                        if let Err(e) = d.set_persist(persist) {
+                            warn!("ignoring error: {}", e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::ConfirmUsbPersistOff(switch, id) => self.confirm_usb_persist_off(&switch, id),
+            Action::ConfirmDiskAutoSuspend(switch, id) => {
+                self.confirm_disk_autosuspend(&switch, id)
+            }
+            Action::SetUsbAuthorizedDefault(id, policy) => {
+                match policy.parse() {
+                    Ok(policy) => {
+                        for d in inner.state.borrow_mut().usb_devices.iter_mut() {
+                            if d.get_id() == id {
+                                if let Err(e) = d.set_authorized_default(policy) {
+                                    warn!("ignoring error: {}", e);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => warn!("ignoring error: {}", e),
+                }
+
+                inner.set_changed();
+            }
+            Action::ConfirmUsbDeauthorize(switch, id) => self.confirm_usb_deauthorize(&switch, id),
+            Action::ShowUsbDetails(row, id) => {
+                let details = inner
+                    .state
+                    .borrow()
+                    .usb_devices
+                    .iter()
+                    .find(|d| d.get_id() == id)
+                    .map(|d| self.build_usb_details(d, inner));
+                if let Some(details) = details {
+                    self.show_details_popover(&row, &details);
+                }
+            }
+            Action::ShowPciDetails(row, id) => {
+                let details = inner
+                    .state
+                    .borrow()
+                    .pci_devices
+                    .iter()
+                    .find(|d| d.get_id() == id)
+                    .map(|d| self.build_pci_details(d));
+                if let Some(details) = details {
+                    self.show_details_popover(&row, &details);
+                }
+            }
             Action::SetPciAutoSuspend(id, autosuspend) => {
                 for d in inner.state.borrow_mut().pci_devices.iter_mut() {
                     if d.get_id() == id {
@@ -808,6 +3215,396 @@ impl GPApplication {
 
                 inner.set_changed();
             }
+            Action::SetPciD3coldAllowed(id, allowed) => {
+                for d in inner.state.borrow_mut().pci_devices.iter_mut() {
+                    if d.get_id() == id {
+                        if let Err(e) = d.set_d3cold_allowed(allowed) {
+                            warn!("can't change D3cold of {}: {}", id, e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetPciAllowWakeup(id, allowed) => {
+                for d in inner.state.borrow_mut().pci_devices.iter_mut() {
+                    if d.get_id() == id {
+                        if let Err(e) = d.set_allow_wakeup(allowed) {
+                            warn!("can't change wakeup of {}: {}", id, e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetPciLinkState(id, state, enabled) => {
+                for d in inner.state.borrow_mut().pci_devices.iter_mut() {
+                    if d.get_id() == id {
+                        if let Err(e) = d.set_link_state(state, enabled) {
+                            warn!("can't change link of {}: {}", id, e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetAspmPolicy(policy) => {
+                let changed = match &mut inner.state.borrow_mut().aspm_policy {
+                    Some(p) if p.current() != policy => match p.set(&policy) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("{}", e);
+                            false
+                        }
+                    },
+                    _ => false,
+                };
+
+                if changed {
+                    inner.set_changed();
+                }
+            }
+            Action::SetSataPolicy(id, policy) => {
+                for h in inner.state.borrow_mut().sata_hosts.iter_mut() {
+                    if h.get_id() == id {
+                        if let Err(e) = h.set_policy(&policy) {
+                            warn!("can't change policy of {}: {}", id, e);
+                        }
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetDiskAutoSuspend(id, autosuspend) => {
+                for d in inner.state.borrow_mut().disks.iter_mut() {
+                    if d.get_id() == id {
+                        d.set_autosuspend(autosuspend);
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetDiskAutoSuspendDelay(source, id, delay) => {
+                match humantime::parse_duration(&delay) {
+                    Ok(duration) => {
+                        self.set_error(&source, None);
+                        for d in inner.state.borrow_mut().disks.iter_mut() {
+                            if d.get_id() == id {
+                                d.set_autosuspend_delay(duration.as_millis() as u64);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        self.set_error(&source, Some(&format!("{}", e)));
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetAudioPowerSave(seconds) => {
+                if let Some(audio) = &mut inner.state.borrow_mut().audio {
+                    audio.set_power_save(seconds);
+                }
+
+                inner.set_changed();
+            }
+            Action::SetAudioPowerSaveController(enabled) => {
+                if let Some(audio) = &mut inner.state.borrow_mut().audio {
+                    if let Err(e) = audio.set_power_save_controller(enabled) {
+                        warn!("{}", e);
+                    }
+                }
+
+                inner.set_changed();
+            }
+            Action::SetCpuGovernor(id, governor) => {
+                for p in inner.state.borrow_mut().cpu_policies.iter_mut() {
+                    if id.is_none() || id.as_deref() == Some(p.get_id()) {
+                        if let Err(e) = p.set_governor(&governor) {
+                            warn!("{}", e);
+                        }
+                    }
+                }
+                if id.is_none() {
+                    self.fill_cpu_list();
+                }
+
+                inner.set_changed();
+            }
+            Action::SetCpuEpp(id, epp) => {
+                for p in inner.state.borrow_mut().cpu_policies.iter_mut() {
+                    if id.is_none() || id.as_deref() == Some(p.get_id()) {
+                        if let Err(e) = p.set_epp(&epp) {
+                            warn!("{}", e);
+                        }
+                    }
+                }
+                if id.is_none() {
+                    self.fill_cpu_list();
+                }
+
+                inner.set_changed();
+            }
+            Action::PersistAudio => {
+                let snippet = inner
+                    .state
+                    .borrow()
+                    .audio
+                    .as_ref()
+                    .map(|a| a.modprobe_snippet());
+                if let Some(snippet) = snippet {
+                    self.confirm_write_file(
+                        "Save audio settings for the next boots?",
+                        audio::MODPROBE_PATH,
+                        snippet,
+                    );
+                }
+            }
+            Action::WriteFile(dialog, path, content) => {
+                glib::MainContext::default().spawn_local(async move {
+                    match crate::fs::write_string_privileged(Path::new(&path), content).await {
+                        Ok(()) => {
+                            info!("wrote {}", path);
+                            unsafe {
+                                dialog.destroy();
+                            }
+                        }
+                        Err(e) => {
+                            error!("error writing {}: {}", path, e);
+                            dialog.set_property_message_type(gtk::MessageType::Error);
+                            dialog.set_property_secondary_text(Some(&format!(
+                                "Writing {} failed: {}",
+                                path, e
+                            )));
+                            dialog.set_sensitive(true);
+                        }
+                    }
+                });
+            }
+            Action::SetPlatformProfile(profile) => {
+                let changed = match &mut inner.state.borrow_mut().platform_profile {
+                    Some(p) if p.current() != profile => match p.set(&profile) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("{}", e);
+                            false
+                        }
+                    },
+                    _ => false,
+                };
+
+                if changed {
+                    inner.set_changed();
+                }
+            }
+            Action::SetMemSleep(mode) => {
+                let changed = match &mut inner.state.borrow_mut().mem_sleep {
+                    Some(m) if m.current() != mode => match m.set(&mode) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("{}", e);
+                            false
+                        }
+                    },
+                    _ => false,
+                };
+
+                if changed {
+                    inner.set_changed();
+                }
+            }
+            Action::PersistMemSleep => {
+                let snippet = inner
+                    .state
+                    .borrow()
+                    .mem_sleep
+                    .as_ref()
+                    .map(|m| m.tmpfiles_snippet());
+                if let Some(snippet) = snippet {
+                    self.confirm_write_file(
+                        "Keep this suspend mode after rebooting?",
+                        sleep::TMPFILES_PATH,
+                        snippet,
+                    );
+                }
+            }
+            Action::SetBatteryThresholds(id, start, end) => {
+                let start_value = start.as_ref().map_or(0, |s| s.get_value_as_int() as u8);
+                let end_value = end.as_ref().map_or(100, |s| s.get_value_as_int() as u8);
+                let mut res = Ok(());
+                for b in inner.state.borrow_mut().batteries.iter_mut() {
+                    if b.get_id() == id {
+                        res = b.set_thresholds(start_value, end_value);
+                    }
+                }
+
+                let error = res.err().map(|e| e.to_string());
+                for spin in start.iter().chain(end.iter()) {
+                    self.set_widget_error(spin.upcast_ref(), error.is_some());
+                    spin.set_tooltip_text(error.as_deref());
+                }
+
+                inner.set_changed();
+            }
+            Action::SaveProfile(name) => {
+                let profile = inner.state.borrow().to_profile(&name);
+                match profile::save_profile(&profile) {
+                    Ok(()) => {
+                        info!("saved profile '{}'", name);
+                        self.fill_profile_list();
+                    }
+                    Err(e) => error!("error saving profile: {}", e),
+                }
+            }
+            Action::LoadProfile(name) => match profile::load_profile(&name) {
+                Ok(profile) => {
+                    inner.state.borrow_mut().apply_profile(&profile);
+                    self.rebuild_lists();
+                    inner.set_changed();
+                }
+                Err(e) => error!("error loading profile: {}", e),
+            },
+            Action::DeleteProfile(name) => {
+                if let Err(e) = profile::delete_profile(&name) {
+                    error!("error deleting profile: {}", e);
+                }
+                self.fill_profile_list();
+            }
+            Action::UpdateAutoSwitch => {
+                get_widget!(auto_switch_switch, gtk::Switch, @inner);
+                get_widget!(auto_switch_ac_combo, gtk::ComboBoxText, @inner);
+                get_widget!(auto_switch_battery_combo, gtk::ComboBoxText, @inner);
+
+                let profile_id = |combo: &gtk::ComboBoxText| {
+                    combo
+                        .get_active_id()
+                        .map(|id| id.to_string())
+                        .filter(|id| id != NO_PROFILE)
+                };
+                let auto_switch = AutoSwitch {
+                    enabled: auto_switch_switch.get_active(),
+                    on_ac: profile_id(&auto_switch_ac_combo),
+                    on_battery: profile_id(&auto_switch_battery_combo),
+                };
+                auto_switch_ac_combo.set_sensitive(auto_switch.enabled);
+                auto_switch_battery_combo.set_sensitive(auto_switch.enabled);
+
+                // refilling the combos gets here without any actual change
+                if auto_switch != inner.state.borrow().auto_switch {
+                    if let Err(e) = profile::save_auto_switch(&auto_switch) {
+                        error!("error saving automatic switching: {}", e);
+                    }
+                    inner.state.borrow_mut().auto_switch = auto_switch;
+                }
+            }
+            Action::PowerSourceChanged(on_ac) => {
+                let source = if on_ac { "AC" } else { "battery" };
+                let (name, busy, changed) = {
+                    let state = inner.state.borrow();
+                    let name = state.auto_switch.profile_for(on_ac).map(|n| n.to_owned());
+                    (name, state.applying || state.measuring, state.changed)
+                };
+                match name {
+                    // the profile is applied once the current changes are written
+                    // or measured
+                    Some(_) if busy => {
+                        info!("switched to {} while applying changes, waiting", source);
+                        inner.state.borrow_mut().pending_power_source = Some(on_ac);
+                    }
+                    Some(name) if changed => info!(
+                        "switched to {}, not applying profile '{}' over unapplied changes",
+                        source, name
+                    ),
+                    Some(name) => match profile::load_profile(&name) {
+                        Ok(profile) => {
+                            info!("switched to {}, applying profile '{}'", source, name);
+                            {
+                                // the same as refreshing, loading the profile and applying it
+                                let mut state = inner.state.borrow_mut();
+                                state.reload();
+                                state.apply_profile(&profile);
+                            }
+                            self.rebuild_lists();
+                            activate!(inner.sender, Action::ApplyChanges);
+                        }
+                        Err(e) => error!("error loading profile '{}': {}", name, e),
+                    },
+                    None => info!("switched to {}", source),
+                }
+            }
+            Action::StartMeasure(seconds) => {
+                get_widget!(measure_progress, gtk::ProgressBar, @inner);
+                get_widget!(measure_start_button, gtk::Button, @inner);
+
+                let (changed, errors) = {
+                    let state = inner.state.borrow();
+                    (state.changed, state.errors)
+                };
+                if !changed || errors > 0 {
+                    measure_progress.set_fraction(0.0);
+                    measure_progress.set_text(Some(if errors > 0 {
+                        "Fix the invalid settings before measuring"
+                    } else {
+                        "No pending changes to measure"
+                    }));
+                } else {
+                    info!("measuring the changes over {} s", seconds);
+                    measure_start_button.set_sensitive(false);
+                    inner.set_measuring(true);
+                    glib::MainContext::default().spawn_local({
+                        let state = inner.state.clone();
+                        let sender = inner.sender.clone();
+                        async move {
+                            let res = measure_changes(state, &sender, seconds).await;
+                            activate!(sender, Action::MeasureDone(res.map_err(|e| e.to_string())));
+                        }
+                    });
+                }
+            }
+            Action::MeasureProgress(fraction, text) => {
+                get_widget!(measure_progress, gtk::ProgressBar, @inner);
+                measure_progress.set_fraction(fraction);
+                measure_progress.set_text(Some(&text));
+            }
+            Action::MeasureDone(res) => {
+                get_widget!(measure_progress, gtk::ProgressBar, @inner);
+                get_widget!(measure_start_button, gtk::Button, @inner);
+                get_widget!(measure_export_button, gtk::Button, @inner);
+                measure_start_button.set_sensitive(true);
+                inner.set_measuring(false);
+
+                match res {
+                    Ok((before, after)) => {
+                        info!("measured {:?} W, then {:?} W", before.mean(), after.mean());
+                        measure_progress.set_fraction(1.0);
+                        measure_progress.set_text(Some("Done"));
+                        self.fill_measure_list(&before, &after);
+                        inner.state.borrow_mut().measurement = Some((before, after));
+                        measure_export_button.set_sensitive(true);
+                    }
+                    Err(e) => {
+                        error!("error measuring: {}", e);
+                        measure_progress.set_fraction(0.0);
+                        measure_progress.set_text(Some(&format!("Measurement failed: {}", e)));
+                    }
+                }
+
+                let pending = inner.state.borrow_mut().pending_power_source.take();
+                if let Some(on_ac) = pending {
+                    activate!(inner.sender, Action::PowerSourceChanged(on_ac));
+                }
+            }
+            Action::ExportMeasure => self.export_measurement(),
+            Action::ShowWakeupDevice(subsystem, id) => self.show_wakeup_device(&subsystem, &id),
+            Action::SetAcpiWakeup(name, enabled) => {
+                for d in inner.state.borrow_mut().acpi_wakeup.iter_mut() {
+                    if d.get_name() == name {
+                        d.set_enabled(enabled);
+                    }
+                }
+
+                inner.set_changed();
+            }
             Action::ShowPane(pane) => {
                 get_widget!(
                     main_stack,
@@ -834,14 +3631,228 @@ impl GPApplication {
     }
 }
 
+fn clear_list(list_box: &gtk::ListBox) {
+    list_box.foreach(clone!(@weak list_box => move |item| {
+        list_box.remove(item);
+    }));
+}
+
+fn add_category(category_list: &gtk::ListBox, mnemonic: &str, action: &str) -> gtk::ListBoxRow {
+    let label = gtk::Label::with_mnemonic(Some(mnemonic));
+    label.set_margin_top(6);
+    label.set_margin_bottom(6);
+    label.set_margin_start(18);
+    label.set_halign(gtk::Align::Start);
+    let row = gtk::ListBoxRow::new();
+    row.add(&label);
+    row.set_action_name(Some(action));
+    category_list.add(&row);
+
+    row
+}
+
+fn make_unavailable_label(text: &str) -> gtk::Label {
+    let label = gtk::Label::new(Some(text));
+    label.set_margin_top(12);
+    label.set_margin_bottom(12);
+    label.get_style_context().add_class("unsupported_label");
+
+    label
+}
+
+fn make_unsupported_label() -> gtk::Label {
+    let label = gtk::Label::new(Some("Not supported"));
+    label.set_halign(gtk::Align::Start);
+    label.get_style_context().add_class("unsupported_label");
+    label.set_tooltip_text(Some(
+        "The device or the hub it is connected to doesn't support this",
+    ));
+
+    label
+}
+
+fn add_capability_rows(grid: &gtk::Grid, mut line: i32, caps: &PciCapabilities) {
+    let yes_no = |b: bool| if b { "Yes" } else { "No" };
+    let mut add_line = |name: &str, value: &str| {
+        add_detail_row(grid, line, name, value);
+        line += 1;
+    };
+    match &caps.power_management {
+        Some(pm) => {
+            add_line("D1 support", yes_no(pm.d1));
+            add_line("D2 support", yes_no(pm.d2));
+            if pm.pme_from.is_empty() {
+                add_line("Wakeup (PME) from", "None");
+            } else {
+                add_line("Wakeup (PME) from", &pm.pme_from.join(" "));
+            }
+        }
+        None => add_line("Power management", "Not supported"),
+    }
+    if let Some(link) = &caps.link {
+        add_line("Max link speed", link.max_speed.unwrap_or("Unknown"));
+        add_line("Max link width", &format!("x{}", link.max_width));
+        add_line("ASPM support", link.get_aspm_description());
+        add_line("Clock PM support", yes_no(link.clock_pm));
+        add_line(
+            "L1 substates",
+            &caps
+                .l1_substates
+                .as_ref()
+                .map(|l1| l1.get_description())
+                .unwrap_or_else(|| "Not supported".to_owned()),
+        );
+    }
+}
+
+fn make_error_label(error: &anyhow::Error) -> gtk::Label {
+    let label = gtk::Label::new(Some(&format!("{}", error)));
+    label.get_style_context().add_class("unsupported_label");
+    label.set_halign(gtk::Align::Start);
+    label.set_line_wrap(true);
+    label.set_max_width_chars(40);
+
+    label
+}
+
+fn add_detail_row(grid: &gtk::Grid, line: i32, name: &str, value: &str) {
+    let name_label = gtk::Label::new(Some(name));
+    name_label.get_style_context().add_class("dim-label");
+    name_label.set_halign(gtk::Align::End);
+    let value_label = gtk::Label::new(Some(value));
+    value_label.set_halign(gtk::Align::Start);
+    value_label.set_selectable(true);
+
+    grid.attach(&name_label, 0, line, 1, 1);
+    grid.attach(&value_label, 1, line, 1, 1);
+}
+
+/// Measures the power draw, applies the pending changes like the apply
+/// button, and measures it again.
+async fn measure_changes(
+    state: Rc<RefCell<State>>,
+    sender: &Sender<Action>,
+    seconds: u32,
+) -> Result<(Measurement, Measurement)> {
+    let batteries = state.borrow().batteries.clone();
+
+    let before = measure_power(&batteries, seconds, sender, ("Before", 0.0)).await?;
+
+    activate!(
+        sender,
+        Action::MeasureProgress(0.5, "Applying changes".to_owned())
+    );
+    apply_changes(state).await?;
+    activate!(sender, Action::Refresh);
+    glib::timeout_future_seconds(MEASURE_SETTLE_TIME).await;
+
+    let after = measure_power(&batteries, seconds, sender, ("After", 0.5)).await?;
+
+    Ok((before, after))
+}
+
+/// Samples the power draw every second, for one half of the progress.
+async fn measure_power(
+    batteries: &[Battery],
+    seconds: u32,
+    sender: &Sender<Action>,
+    (phase, progress): (&str, f64),
+) -> Result<Measurement> {
+    let mut meter = PowerMeter::new(batteries)?;
+    let mut measurement = Measurement::default();
+
+    for elapsed in 1..=seconds {
+        glib::timeout_future_seconds(1).await;
+        if let Some(watts) = meter.read()? {
+            measurement.push(PowerSample {
+                time: elapsed as f64,
+                watts,
+            });
+        }
+
+        let fraction = progress + elapsed as f64 / seconds as f64 / 2.0;
+        let text = format!("{}: {} s left", phase, seconds - elapsed);
+        activate!(sender, Action::MeasureProgress(fraction, text));
+    }
+
+    if measurement.samples().is_empty() {
+        return Err(anyhow!(
+            "the battery didn't report any draw, try measuring longer"
+        ));
+    }
+
+    Ok(measurement)
+}
+
 async fn apply_changes(state: Rc<RefCell<State>>) -> Result<()> {
-    for d in &state.borrow().usb_devices {
+    // the writes wait for the user to authenticate, the state must stay
+    // usable in the meantime
+    let mut state = state.borrow().clone();
+    // errors the firmware can cause on its own don't stop the other writes
+    let mut errors = Vec::new();
+
+    // some drivers change the CPU settings along with the platform profile, so
+    // it's written first and the settings the user didn't touch are re-read
+    if let Some(platform_profile) = &state.platform_profile {
+        if platform_profile.save().await? {
+            state.cpu_policies.iter_mut().for_each(|p| p.refresh());
+        }
+    }
+    if let Some(mem_sleep) = &state.mem_sleep {
+        mem_sleep.save().await?;
+    }
+
+    // the global policy resets the per-link states, so it must be written first
+    if let Some(policy) = &state.aspm_policy {
+        match policy.save().await {
+            Ok(true) => state.pci_devices.iter_mut().for_each(|d| d.refresh_link()),
+            Ok(false) => (),
+            Err(e) => errors.push(format!("ASPM policy: {}", e)),
+        }
+    }
+
+    for d in &state.usb_devices {
+        d.save().await?;
+    }
+
+    for d in &state.pci_devices {
         d.save().await?;
     }
 
-    for d in &state.borrow().pci_devices {
+    // the ACPI table also changes the wakeup of the devices bound to it, so its
+    // entries are written after them and win over their own setting; an entry
+    // that doesn't take doesn't stop the other writes
+    for d in &state.acpi_wakeup {
+        if let Err(e) = d.save().await {
+            errors.push(format!("ACPI wakeup: {}", e));
+        }
+    }
+
+    for h in &state.sata_hosts {
+        if let Err(e) = h.save().await {
+            errors.push(format!("{}: {}", h.get_id(), e));
+        }
+    }
+
+    for d in &state.disks {
         d.save().await?;
     }
 
+    if let Some(audio) = &state.audio {
+        audio.save().await?;
+    }
+
+    for p in &state.cpu_policies {
+        p.save().await?;
+    }
+
+    for b in &state.batteries {
+        b.save().await?;
+    }
+
+    if !errors.is_empty() {
+        bail!("{}", errors.join(", "));
+    }
+
     Ok(())
 }
@@ -0,0 +1,205 @@
+use crate::fs::write_string_privileged;
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::Path;
+
+const PARAMETERS_PATH: &str = "/sys/module/snd_hda_intel/parameters";
+const CARDS_PATH: &str = "/proc/asound/cards";
+/// Where [`AudioPowerSave::modprobe_snippet`] is meant to be written.
+pub const MODPROBE_PATH: &str = "/etc/modprobe.d/gpower-tweaks-audio.conf";
+
+/// Power saving parameters of the HDA audio driver (`snd_hda_intel`).
+#[derive(Clone, Debug)]
+pub struct AudioPowerSave {
+    power_save: i32,
+    power_save_controller: Option<bool>,
+    /// The parameters when they were read.
+    read: (i32, Option<bool>),
+    cards: Vec<SoundCard>,
+}
+
+/// A sound card, as listed in `/proc/asound/cards`.
+#[derive(Clone, Debug)]
+pub struct SoundCard {
+    pub index: u32,
+    pub id: String,
+    pub driver: String,
+    pub name: String,
+}
+
+impl SoundCard {
+    /// Whether the card is driven by `snd_hda_intel`, and so affected by its parameters.
+    pub fn is_hda(&self) -> bool {
+        self.driver == "HDA-Intel"
+    }
+}
+
+impl AudioPowerSave {
+    /// The idle time in seconds before the codec is powered down, 0 or less
+    /// if never.
+    pub fn power_save(&self) -> i32 {
+        self.power_save
+    }
+
+    pub fn set_power_save(&mut self, seconds: i32) {
+        self.power_save = seconds;
+    }
+
+    /// Whether the controller is powered down along with the codec.
+    pub fn power_save_controller(&self) -> Option<bool> {
+        self.power_save_controller
+    }
+
+    pub fn set_power_save_controller(&mut self, enabled: bool) -> Result<()> {
+        if self.power_save_controller.is_none() {
+            bail!("driver doesn't support power_save_controller");
+        }
+        self.power_save_controller = Some(enabled);
+
+        Ok(())
+    }
+
+    pub fn cards(&self) -> &[SoundCard] {
+        &self.cards
+    }
+
+    /// The settings, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let mut settings = vec![("power_save".to_owned(), self.power_save.to_string())];
+        if let Some(enabled) = self.power_save_controller {
+            let value = if enabled { "Y" } else { "N" };
+            settings.push(("power_save_controller".to_owned(), value.to_owned()));
+        }
+
+        settings
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "power_save" => self.power_save = value.parse()?,
+            "power_save_controller" => match value {
+                "Y" => self.set_power_save_controller(true)?,
+                "N" => self.set_power_save_controller(false)?,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            _ => bail!("unknown audio setting '{}'", key),
+        }
+
+        Ok(())
+    }
+
+    /// A `modprobe.d` configuration setting the current parameters when the
+    /// driver is loaded.
+    pub fn modprobe_snippet(&self) -> String {
+        let mut options = format!("options snd_hda_intel power_save={}", self.power_save);
+        if let Some(enabled) = self.power_save_controller {
+            options.push_str(if enabled {
+                " power_save_controller=Y"
+            } else {
+                " power_save_controller=N"
+            });
+        }
+
+        format!(
+            "# Written by gpower-tweaks\n# Audio codec power saving, see Documentation/sound/designs/powersave.rst\n{}\n",
+            options
+        )
+    }
+
+    /// Writes the parameters that were changed since they were read.
+    pub async fn save(&self) -> Result<()> {
+        let parameters = Path::new(PARAMETERS_PATH);
+        let (read_power_save, read_power_save_controller) = self.read;
+
+        trace!(
+            "saving '{}' with ({}, {:?})",
+            PARAMETERS_PATH,
+            self.power_save,
+            self.power_save_controller
+        );
+
+        if self.power_save != read_power_save {
+            write_string_privileged(&parameters.join("power_save"), self.power_save.to_string())
+                .await?;
+        }
+        if let (Some(enabled), true) = (
+            self.power_save_controller,
+            self.power_save_controller != read_power_save_controller,
+        ) {
+            let text = if enabled { "Y" } else { "N" };
+            write_string_privileged(&parameters.join("power_save_controller"), text.to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the parameters of the HDA driver, if it is loaded.
+pub fn read_power_save() -> Result<AudioPowerSave> {
+    let parameters = Path::new(PARAMETERS_PATH);
+
+    let power_save = fs::read_to_string(parameters.join("power_save"))?
+        .trim()
+        .parse()?;
+    let power_save_controller = fs::read_to_string(parameters.join("power_save_controller"))
+        .ok()
+        .map(|s| s.trim() == "Y");
+    let cards = match fs::read_to_string(CARDS_PATH) {
+        Ok(content) => parse_cards(&content),
+        Err(e) => {
+            warn!("ignoring error reading sound cards: {}", e);
+            Vec::new()
+        }
+    };
+
+    Ok(AudioPowerSave {
+        power_save,
+        power_save_controller,
+        read: (power_save, power_save_controller),
+        cards,
+    })
+}
+
+/// Parses `/proc/asound/cards`, which looks like:
+///
+/// ```text
+///  0 [PCH            ]: HDA-Intel - HDA Intel PCH
+///                       HDA Intel PCH at 0xf7f10000 irq 32
+/// ```
+fn parse_cards(content: &str) -> Vec<SoundCard> {
+    let mut cards = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        let bracket = match line.find('[') {
+            Some(b) => b,
+            None => continue,
+        };
+        let index = match line[..bracket].trim().parse() {
+            Ok(index) => index,
+            // continuation lines don't start with the index
+            Err(_) => continue,
+        };
+        let rest = &line[bracket + 1..];
+        let (id, rest) = match rest.find("]:") {
+            Some(end) => (rest[..end].trim(), rest[end + 2..].trim()),
+            None => continue,
+        };
+        let (driver, name) = match rest.find(" - ") {
+            Some(sep) => (rest[..sep].trim(), rest[sep + 3..].trim()),
+            None => (rest, ""),
+        };
+
+        cards.push(SoundCard {
+            index,
+            id: id.to_owned(),
+            driver: driver.to_owned(),
+            name: name.to_owned(),
+        });
+    }
+
+    cards
+}
@@ -0,0 +1,210 @@
+use crate::fs::write_string_privileged;
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+
+/// A cpufreq policy, controlling the frequency of one or more CPUs.
+#[derive(Clone, Debug)]
+pub struct CpuPolicy {
+    id: String,
+    path: PathBuf,
+    cpus: String,
+    driver: Option<String>,
+    governor: String,
+    available_governors: Vec<String>,
+    epp: Option<String>,
+    available_epp: Vec<String>,
+    /// The governor and preference when they were read.
+    read: (String, Option<String>),
+}
+
+impl CpuPolicy {
+    /// The name of the policy directory (`policy0`...).
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// The CPUs this policy applies to, as a list like `0 1 2 3`.
+    pub fn cpus(&self) -> &str {
+        &self.cpus
+    }
+
+    /// The scaling driver (`intel_pstate`, `amd-pstate`, `acpi-cpufreq`...).
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
+
+    pub fn governor(&self) -> &str {
+        &self.governor
+    }
+
+    pub fn available_governors(&self) -> &[String] {
+        &self.available_governors
+    }
+
+    pub fn set_governor(&mut self, governor: &str) -> Result<()> {
+        if !self.available_governors.iter().any(|g| g == governor) {
+            bail!("governor '{}' is not available for {}", governor, self.id);
+        }
+        self.governor = governor.to_owned();
+
+        Ok(())
+    }
+
+    /// The energy-performance preference, if the driver supports it.
+    pub fn epp(&self) -> Option<&str> {
+        self.epp.as_deref()
+    }
+
+    pub fn available_epp(&self) -> &[String] {
+        &self.available_epp
+    }
+
+    pub fn set_epp(&mut self, epp: &str) -> Result<()> {
+        if self.epp.is_none() {
+            bail!("{} doesn't support energy-performance preferences", self.id);
+        }
+        if !self.available_epp.iter().any(|e| e == epp) {
+            bail!("preference '{}' is not available for {}", epp, self.id);
+        }
+        self.epp = Some(epp.to_owned());
+
+        Ok(())
+    }
+
+    /// The settings of this policy, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let mut settings = vec![("scaling_governor".to_owned(), self.governor.clone())];
+        if let Some(epp) = &self.epp {
+            settings.push(("energy_performance_preference".to_owned(), epp.clone()));
+        }
+
+        settings
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "scaling_governor" => self.set_governor(value),
+            "energy_performance_preference" => self.set_epp(value),
+            _ => bail!("unknown cpu setting '{}'", key),
+        }
+    }
+
+    /// Takes the current values of the settings that weren't changed since
+    /// they were read, e.g. after the platform profile changed them.
+    pub fn refresh(&mut self) {
+        let (read_governor, read_epp) = &self.read;
+        let governor_changed = &self.governor != read_governor;
+        let epp_changed = &self.epp != read_epp;
+
+        if let Ok(policy) = make_policy(self.path.clone()) {
+            if !governor_changed {
+                self.governor = policy.governor.clone();
+            }
+            if !epp_changed {
+                self.epp = policy.epp.clone();
+            }
+            self.read = policy.read;
+        }
+    }
+
+    /// Writes the governor, then the preference.
+    ///
+    /// Only what was changed since it was read, and differs from the current
+    /// values, is written: some drivers refuse any write to the preference
+    /// while the `performance` governor is used, even an unchanged one.
+    pub async fn save(&self) -> Result<()> {
+        let current = |name: &str| {
+            fs::read_to_string(self.path.join(name))
+                .ok()
+                .map(|s| s.trim().to_owned())
+        };
+        let (read_governor, read_epp) = &self.read;
+
+        if &self.governor != read_governor
+            && current("scaling_governor").as_ref() != Some(&self.governor)
+        {
+            trace!("saving '{}' with {}", self.path.display(), self.governor);
+            write_string_privileged(&self.path.join("scaling_governor"), self.governor.clone())
+                .await?;
+        }
+
+        if &self.epp != read_epp && current("energy_performance_preference") != self.epp {
+            if let Some(epp) = &self.epp {
+                trace!("saving '{}' with {}", self.path.display(), epp);
+                write_string_privileged(
+                    &self.path.join("energy_performance_preference"),
+                    epp.clone(),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn list_policies() -> Result<Vec<CpuPolicy>> {
+    debug!("listing cpufreq policies");
+
+    let mut policies = Vec::new();
+
+    let dir = PathBuf::from("/sys/devices/system/cpu/cpufreq/");
+    for entry in fs::read_dir(&dir)? {
+        match_warn!(entry, "ignoring error while enumerating policies: {}", entry => {
+            if entry.file_name().to_string_lossy().starts_with("policy") {
+                let policy = make_policy(entry.path());
+                match_warn!(policy, "ignoring error reading policy: {}", policy => {
+                    policies.push(policy);
+                });
+            }
+        });
+    }
+
+    policies.sort_by_key(|p| policy_number(&p.id));
+
+    Ok(policies)
+}
+
+fn policy_number(id: &str) -> u32 {
+    id.trim_start_matches("policy").parse().unwrap_or(u32::MAX)
+}
+
+fn make_policy(path: PathBuf) -> Result<CpuPolicy> {
+    let id = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get policy name"),
+    };
+    let read = |name: &str| {
+        fs::read_to_string(path.join(name))
+            .ok()
+            .map(|s| s.trim().to_owned())
+    };
+    let read_list = |name: &str| {
+        read(name)
+            .map(|s| s.split_whitespace().map(|s| s.to_owned()).collect())
+            .unwrap_or_default()
+    };
+
+    let governor = read("scaling_governor")
+        .ok_or_else(|| anyhow!("{} has no scaling governor", path.display()))?;
+    let epp = read("energy_performance_preference");
+
+    let policy = CpuPolicy {
+        id,
+        cpus: read("related_cpus")
+            .or_else(|| read("affected_cpus"))
+            .unwrap_or_default(),
+        driver: read("scaling_driver"),
+        available_governors: read_list("scaling_available_governors"),
+        available_epp: read_list("energy_performance_available_preferences"),
+        read: (governor.clone(), epp.clone()),
+        governor,
+        epp,
+        path: path.clone(),
+    };
+
+    Ok(policy)
+}
@@ -0,0 +1,214 @@
+use crate::fs::{block_devices_under, mounted_filesystems, write_string_privileged};
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+
+/// A SCSI disk (SATA, USB mass storage...) whose runtime PM can be controlled.
+#[derive(Clone, Debug)]
+pub struct Disk {
+    id: String,
+    device_path: PathBuf,
+    vendor: Option<String>,
+    model: Option<String>,
+    wwid: Option<String>,
+    block_devices: Vec<String>,
+    autosuspend: bool,
+    delay: u64,
+    /// The autosuspend and delay settings when they were read, the delay is
+    /// `None` when it was -1 (never suspend).
+    read_power: (bool, Option<u64>),
+}
+
+impl Disk {
+    /// The SCSI address of the disk (`host:channel:target:lun`).
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_name(&self) -> String {
+        self.model
+            .clone()
+            .unwrap_or_else(|| "Unknown disk".to_owned())
+    }
+
+    pub fn get_description(&self) -> String {
+        let mut desc = String::new();
+
+        if let Some(vendor) = &self.vendor {
+            desc.push_str(vendor);
+        }
+        for name in &self.block_devices {
+            if !desc.is_empty() {
+                desc.push(' ');
+            }
+            desc.push_str("/dev/");
+            desc.push_str(name);
+        }
+
+        desc
+    }
+
+    /// What identifies this disk in a [`Profile`](crate::profile::Profile):
+    /// its world wide identifier if it has one, its SCSI address otherwise.
+    pub fn profile_target(&self) -> &str {
+        self.wwid.as_deref().unwrap_or(&self.id)
+    }
+
+    pub fn can_autosuspend(&self) -> bool {
+        self.autosuspend
+    }
+
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    pub fn set_autosuspend(&mut self, autosuspend: bool) {
+        self.autosuspend = autosuspend;
+    }
+
+    pub fn set_autosuspend_delay(&mut self, delay: u64) {
+        self.delay = delay;
+    }
+
+    /// Mount points of the filesystems on this disk.
+    pub fn mounted_filesystems(&self) -> Vec<String> {
+        let block_devices = block_devices_under(&self.device_path);
+        match mounted_filesystems(&block_devices) {
+            Ok(mount_points) => mount_points,
+            Err(e) => {
+                warn!("ignoring error reading mounts: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// The settings of this disk, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let control = if self.autosuspend { "auto" } else { "on" };
+
+        vec![
+            ("control".to_owned(), control.to_owned()),
+            ("autosuspend_delay_ms".to_owned(), self.delay.to_string()),
+        ]
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "control" => match value {
+                "auto" => self.autosuspend = true,
+                "on" => self.autosuspend = false,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            "autosuspend_delay_ms" => self.delay = value.parse()?,
+            _ => bail!("unknown disk setting '{}'", key),
+        }
+
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let control_path = self.device_path.join("power/control");
+        let autosuspend_delay_path = self.device_path.join("power/autosuspend_delay_ms");
+
+        let control_text = if self.autosuspend {
+            "auto".to_string()
+        } else {
+            "on".to_string()
+        };
+        let autosuspend_delay_text = self.delay.to_string();
+
+        trace!(
+            "saving '{}' with ({}, {})",
+            self.device_path.to_string_lossy(),
+            control_text,
+            autosuspend_delay_text
+        );
+
+        let (read_autosuspend, read_delay) = self.read_power;
+        if self.autosuspend != read_autosuspend {
+            write_string_privileged(&control_path, control_text).await?;
+        }
+        if Some(self.delay) != read_delay {
+            write_string_privileged(&autosuspend_delay_path, autosuspend_delay_text).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn list_disks() -> Result<Vec<Disk>> {
+    debug!("listing disks");
+
+    let mut disks = Vec::new();
+
+    let dir = PathBuf::from("/sys/class/scsi_disk/");
+    for entry in fs::read_dir(&dir)? {
+        match_warn!(entry, "ignoring error while enumerating disks: {}", entry => {
+            // the runtime PM attributes are on the SCSI device, not the class device
+            match_warn!(entry.path().join("device").canonicalize(), "ignoring disk without device: {}", path => {
+                let disk = make_disk(path);
+                match_warn!(disk, "ignoring error reading disk: {}", disk => {
+                    disks.push(disk);
+                });
+            });
+        });
+    }
+
+    disks.sort_by(|a, b| a.block_devices.cmp(&b.block_devices));
+
+    Ok(disks)
+}
+
+fn make_disk(path: PathBuf) -> Result<Disk> {
+    let id = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get disk name"),
+    };
+    let read = |name: &str| {
+        fs::read_to_string(path.join(name))
+            .ok()
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+    };
+
+    let vendor = read("vendor");
+    let model = read("model");
+    let wwid = read("wwid");
+    let mut block_devices: Vec<String> = fs::read_dir(path.join("block"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    block_devices.sort();
+
+    let autosuspend = fs::read_to_string(path.join("power/control"))?.trim() == "auto";
+    let delay = read("power/autosuspend_delay_ms");
+    let mut disk = Disk {
+        id,
+        device_path: path,
+        vendor,
+        model,
+        wwid,
+        block_devices,
+        autosuspend,
+        delay: 0,
+        read_power: (autosuspend, None),
+    };
+
+    let mut read_delay = None;
+    if let Some(delay) = delay {
+        match delay.parse::<i64>()? {
+            -1 => disk.autosuspend = false,
+            i => {
+                disk.delay = i as u64;
+                read_delay = Some(disk.delay);
+            }
+        }
+    }
+    disk.read_power = (disk.autosuspend, read_delay);
+
+    Ok(disk)
+}
@@ -20,6 +20,34 @@ pub async fn write_privileged(path: &Path) -> Result<gio::FileOutputStream> {
         "trying to do a privileged write to {}",
         path.to_str().unwrap_or_default()
     );
+    let file = admin_file(path).await?;
+
+    let stream = file
+        .replace_async_future(
+            None,
+            false,
+            gio::FileCreateFlags::NONE,
+            glib::source::PRIORITY_DEFAULT,
+        )
+        .await?;
+
+    Ok(stream)
+}
+
+/// Reads a whole file as root, for the files the kernel only shows in full to root.
+pub async fn read_privileged(path: &Path) -> Result<Vec<u8>> {
+    trace!(
+        "trying to do a privileged read of {}",
+        path.to_str().unwrap_or_default()
+    );
+    let file = admin_file(path).await?;
+
+    let (content, _) = file.load_contents_async_future().await?;
+
+    Ok(content)
+}
+
+async fn admin_file(path: &Path) -> Result<gio::File> {
     let mut admin_path = String::with_capacity(path.as_os_str().len() + 8);
     admin_path.push_str("admin://");
     admin_path.push_str(
@@ -45,14 +73,119 @@ pub async fn write_privileged(path: &Path) -> Result<gio::FileOutputStream> {
         }
     }
 
-    let stream = file
-        .replace_async_future(
-            None,
-            false,
-            gio::FileCreateFlags::NONE,
-            glib::source::PRIORITY_DEFAULT,
-        )
-        .await?;
+    Ok(file)
+}
 
-    Ok(stream)
+/// Reads a sysfs attribute listing choices, where the current one is between
+/// brackets like `s2idle [deep]`: returns the current choice, if any, and
+/// all of them.
+pub fn read_choices(path: &Path) -> Result<(Option<String>, Vec<String>)> {
+    let content = std::fs::read_to_string(path)?;
+    let mut current = None;
+    let mut choices = Vec::new();
+
+    for choice in content.split_whitespace() {
+        if choice.starts_with('[') && choice.ends_with(']') {
+            let choice = &choice[1..choice.len() - 1];
+            current = Some(choice.to_owned());
+            choices.push(choice.to_owned());
+        } else {
+            choices.push(choice.to_owned());
+        }
+    }
+
+    Ok((current, choices))
+}
+
+/// Lists the block devices (disks and their partitions) found below a sysfs device.
+pub fn block_devices_under(device: &Path) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut dirs = vec![device.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                trace!("ignoring error reading {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+
+        // symlinks are skipped by only looking at real directories, sysfs is full of loops
+        for entry in entries.flatten() {
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+
+            if entry.file_name() != "block" {
+                dirs.push(entry.path());
+                continue;
+            }
+
+            for disk in std::fs::read_dir(entry.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                let name = disk.file_name().to_string_lossy().into_owned();
+                // partitions are subdirectories named after their disk
+                for part in std::fs::read_dir(disk.path())
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                {
+                    let part_name = part.file_name().to_string_lossy().into_owned();
+                    if part_name.starts_with(&name) {
+                        found.push(part_name);
+                    }
+                }
+                found.push(name);
+            }
+        }
+    }
+
+    // device-mapper devices (LUKS, LVM) stacked on top of those
+    let mut holders = Vec::new();
+    for name in &found {
+        let holders_path = Path::new("/sys/class/block").join(name).join("holders");
+        for holder in std::fs::read_dir(holders_path)
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            holders.push(holder.file_name().to_string_lossy().into_owned());
+        }
+    }
+    found.append(&mut holders);
+
+    found
+}
+
+/// Returns the mount points of the filesystems mounted from any of these block devices.
+pub fn mounted_filesystems(block_devices: &[String]) -> Result<Vec<String>> {
+    if block_devices.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mounts = std::fs::read_to_string("/proc/mounts")?;
+    let mut mount_points = Vec::new();
+
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        if let (Some(source), Some(mount_point)) = (fields.next(), fields.next()) {
+            if !source.starts_with("/dev/") {
+                continue;
+            }
+            // resolves /dev/mapper/* and /dev/disk/by-*/* links
+            let source = Path::new(source)
+                .canonicalize()
+                .unwrap_or_else(|_| source.into());
+            let name = source.file_name().unwrap_or_default().to_string_lossy();
+            if block_devices.iter().any(|d| *d == name) {
+                mount_points.push(mount_point.replace("\\040", " "));
+            }
+        }
+    }
+
+    Ok(mount_points)
 }
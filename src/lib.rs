@@ -1,7 +1,18 @@
+#[macro_use]
+mod macros;
 #[allow(clippy::clippy::from_over_into)]
 
 pub mod app;
+pub mod audio;
+pub mod cpu;
 pub(crate) mod db;
+pub mod disk;
 pub mod fs;
 pub mod pci;
+pub mod platform;
+pub mod power_supply;
+pub mod profile;
+pub mod sata;
+pub mod sleep;
 pub mod usb;
+pub mod wakeup;
@@ -0,0 +1,12 @@
+/// Logs the error of a `Result` as a warning, or runs `$func` with its value.
+///
+/// The optional `=> $add` runs after the warning, to `continue` a loop for
+/// example.
+macro_rules! match_warn {
+    ($content:expr, $format:expr$(=> $add:expr)? , $bind:ident => $func:expr) => {
+        match $content {
+            Err(e) => {log::warn!($format, e) $(; $add)?},
+            Ok($bind) => $func,
+        }
+    };
+}
@@ -0,0 +1,232 @@
+use crate::fs::{read_choices, write_string_privileged};
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const POLICY_PATH: &str = "/sys/module/pcie_aspm/parameters/policy";
+
+/// A power saving state of a PCIe link that can be controlled from `link/`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkState {
+    L0s,
+    L1,
+    L1_1,
+    L1_2,
+    ClockPm,
+}
+
+impl LinkState {
+    pub const ALL: [LinkState; 5] = [
+        LinkState::L0s,
+        LinkState::L1,
+        LinkState::L1_1,
+        LinkState::L1_2,
+        LinkState::ClockPm,
+    ];
+
+    /// The name of the attribute in the `link/` directory of the device.
+    pub fn attribute(&self) -> &'static str {
+        match self {
+            LinkState::L0s => "l0s_aspm",
+            LinkState::L1 => "l1_aspm",
+            LinkState::L1_1 => "l1_1_aspm",
+            LinkState::L1_2 => "l1_2_aspm",
+            LinkState::ClockPm => "clkpm",
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LinkState::L0s => "L0s",
+            LinkState::L1 => "L1",
+            LinkState::L1_1 => "L1.1",
+            LinkState::L1_2 => "L1.2",
+            LinkState::ClockPm => "ClkPM",
+        }
+    }
+
+    pub fn from_attribute(attribute: &str) -> Option<LinkState> {
+        LinkState::ALL
+            .iter()
+            .copied()
+            .find(|s| s.attribute() == attribute)
+    }
+}
+
+/// ASPM and clock PM settings of the PCIe link above a device.
+///
+/// Each state is only there if the kernel lets us control it, which depends on
+/// what both ends of the link support and on the firmware granting the OS
+/// control over ASPM.
+#[derive(Clone, Debug)]
+pub struct PciLink {
+    path: PathBuf,
+    states: Vec<(LinkState, bool)>,
+    /// The states when the link was read.
+    read_states: Vec<(LinkState, bool)>,
+}
+
+impl PciLink {
+    /// Reads the `link/` directory of a device, if it has one.
+    pub(crate) fn read(device_path: &Path) -> Option<PciLink> {
+        let path = device_path.join("link");
+        if !path.is_dir() {
+            return None;
+        }
+
+        let states = read_states(&path);
+        if states.is_empty() {
+            None
+        } else {
+            Some(PciLink {
+                path,
+                read_states: states.clone(),
+                states,
+            })
+        }
+    }
+
+    /// Reads the states again, after something else changed them.
+    ///
+    /// The states changed since the link was read keep their new value, the
+    /// others take the one from sysfs.
+    pub(crate) fn refresh(&mut self) {
+        let live_states = read_states(&self.path);
+        let entries = self.states.iter_mut().zip(self.read_states.iter_mut());
+        for ((state, enabled), (_, read)) in entries {
+            if let Some((_, live)) = live_states.iter().find(|(s, _)| s == state) {
+                if *enabled == *read {
+                    *enabled = *live;
+                }
+                *read = *live;
+            }
+        }
+    }
+
+    pub fn get(&self, state: LinkState) -> Option<bool> {
+        self.states
+            .iter()
+            .find(|(s, _)| *s == state)
+            .map(|(_, enabled)| *enabled)
+    }
+
+    pub fn set(&mut self, state: LinkState, enabled: bool) -> Result<()> {
+        match self.states.iter_mut().find(|(s, _)| *s == state) {
+            Some((_, value)) => *value = enabled,
+            None => bail!("link doesn't support controlling {}", state.get_name()),
+        }
+
+        Ok(())
+    }
+
+    /// The states that can be controlled on this link.
+    pub fn states(&self) -> &[(LinkState, bool)] {
+        &self.states
+    }
+
+    /// Whether any of the L1 substates is supported but turned off.
+    pub fn has_l1_substates_disabled(&self) -> bool {
+        self.get(LinkState::L1_1) == Some(false) || self.get(LinkState::L1_2) == Some(false)
+    }
+
+    /// Writes the states that were changed since the link was read, the
+    /// others may have been changed by the ASPM policy since.
+    pub(crate) async fn save(&self) -> Result<()> {
+        let changed = self
+            .states
+            .iter()
+            .zip(&self.read_states)
+            .filter(|(state, read)| state != read);
+        for ((state, enabled), _) in changed {
+            let text = if *enabled { "1" } else { "0" };
+            trace!(
+                "saving '{}' with {}",
+                self.path.join(state.attribute()).to_string_lossy(),
+                text
+            );
+            write_string_privileged(&self.path.join(state.attribute()), text.to_owned()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The global ASPM policy of the kernel.
+#[derive(Clone, Debug)]
+pub struct AspmPolicy {
+    current: String,
+    /// The policy when it was read.
+    read: String,
+    available: Vec<String>,
+}
+
+impl AspmPolicy {
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn available(&self) -> &[String] {
+        &self.available
+    }
+
+    pub fn set(&mut self, policy: &str) -> Result<()> {
+        if !self.available.iter().any(|p| p == policy) {
+            bail!("unknown ASPM policy '{}'", policy);
+        }
+
+        self.current = policy.to_owned();
+
+        Ok(())
+    }
+
+    pub fn get_description(policy: &str) -> &'static str {
+        match policy {
+            "default" => "Firmware defaults",
+            "performance" => "Performance (ASPM off)",
+            "powersave" => "Power saving",
+            "powersupersave" => "Power saving, with L1 substates",
+            _ => "Unknown",
+        }
+    }
+
+    /// Writes the policy if it was changed, and tells whether it was.
+    ///
+    /// Writing it changes the states of every link, and fails when the
+    /// firmware doesn't let the OS control ASPM, so it isn't written again
+    /// for nothing.
+    pub async fn save(&self) -> Result<bool> {
+        if self.current == self.read {
+            return Ok(false);
+        }
+
+        trace!("saving '{}' with {}", POLICY_PATH, self.current);
+        write_string_privileged(Path::new(POLICY_PATH), self.current.clone()).await?;
+
+        Ok(true)
+    }
+}
+
+fn read_states(path: &Path) -> Vec<(LinkState, bool)> {
+    let mut states = Vec::new();
+    for state in LinkState::ALL.iter() {
+        if let Ok(value) = fs::read_to_string(path.join(state.attribute())) {
+            states.push((*state, value.trim() == "1"));
+        }
+    }
+
+    states
+}
+
+/// Reads the global ASPM policy, if the kernel has ASPM support.
+pub fn read_policy() -> Result<AspmPolicy> {
+    // like "[default] performance powersave"
+    let (current, available) = read_choices(Path::new(POLICY_PATH))?;
+    let current = current.ok_or_else(|| anyhow!("no current ASPM policy"))?;
+
+    Ok(AspmPolicy {
+        read: current.clone(),
+        current,
+        available,
+    })
+}
@@ -0,0 +1,396 @@
+use anyhow::*;
+
+/// Size of the configuration space header, the only part of the `config` file
+/// that the kernel lets unprivileged users read.
+const HEADER_SIZE: usize = 64;
+const EXTENDED_START: usize = 0x100;
+
+const CAP_ID_POWER_MANAGEMENT: u8 = 0x01;
+const CAP_ID_EXPRESS: u8 = 0x10;
+const EXT_CAP_ID_L1_SUBSTATES: u16 = 0x001e;
+
+/// The power related capabilities a device advertises in its configuration space.
+#[derive(Clone, Debug, Default)]
+pub struct PciCapabilities {
+    pub power_management: Option<PowerManagementCapability>,
+    pub link: Option<LinkCapability>,
+    pub l1_substates: Option<L1SubstatesCapability>,
+}
+
+/// The PCI Power Management capability.
+#[derive(Clone, Debug)]
+pub struct PowerManagementCapability {
+    pub d1: bool,
+    pub d2: bool,
+    /// The states from which the device can signal a wakeup (PME).
+    pub pme_from: Vec<&'static str>,
+}
+
+/// The link capabilities from the PCI Express capability.
+#[derive(Clone, Debug)]
+pub struct LinkCapability {
+    pub max_speed: Option<&'static str>,
+    pub max_width: u8,
+    pub aspm_l0s: bool,
+    pub aspm_l1: bool,
+    pub clock_pm: bool,
+}
+
+/// The L1 PM Substates extended capability.
+#[derive(Clone, Debug)]
+pub struct L1SubstatesCapability {
+    pub aspm_l1_1: bool,
+    pub aspm_l1_2: bool,
+    pub pci_pm_l1_1: bool,
+    pub pci_pm_l1_2: bool,
+}
+
+impl LinkCapability {
+    pub fn get_aspm_description(&self) -> &'static str {
+        match (self.aspm_l0s, self.aspm_l1) {
+            (true, true) => "L0s L1",
+            (true, false) => "L0s",
+            (false, true) => "L1",
+            (false, false) => "Not supported",
+        }
+    }
+}
+
+impl L1SubstatesCapability {
+    pub fn get_description(&self) -> String {
+        let names = [
+            (self.aspm_l1_1, "ASPM L1.1"),
+            (self.aspm_l1_2, "ASPM L1.2"),
+            (self.pci_pm_l1_1, "PCI-PM L1.1"),
+            (self.pci_pm_l1_2, "PCI-PM L1.2"),
+        ];
+        let supported: Vec<_> = names.iter().filter(|(s, _)| *s).map(|(_, n)| *n).collect();
+
+        if supported.is_empty() {
+            "None".to_owned()
+        } else {
+            supported.join(" ")
+        }
+    }
+}
+
+fn read_u16(config: &[u8], offset: usize) -> Option<u16> {
+    let bytes = config.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(config: &[u8], offset: usize) -> Option<u32> {
+    let bytes = config.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn has_capability_list(config: &[u8]) -> bool {
+    read_u16(config, 0x06).unwrap_or_default() & 0x10 != 0
+}
+
+/// Whether the capabilities are past what was read, as only root can read
+/// them.
+pub fn needs_privileges(config: &[u8]) -> bool {
+    config.len() <= HEADER_SIZE && has_capability_list(config)
+}
+
+/// Walks the capability lists of a configuration space, as read from the
+/// `config` file of a device.
+pub fn parse_capabilities(config: &[u8]) -> Result<PciCapabilities> {
+    if config.len() < HEADER_SIZE {
+        bail!("configuration space is too short: {} bytes", config.len());
+    }
+
+    let mut caps = PciCapabilities::default();
+
+    if !has_capability_list(config) {
+        return Ok(caps);
+    }
+    if needs_privileges(config) {
+        bail!(
+            "only the first {} bytes of the configuration space are readable, \
+             reading capabilities needs root privileges",
+            HEADER_SIZE
+        );
+    }
+
+    // cardbus bridges keep the pointer somewhere else
+    let pointer_offset = if config[0x0e] & 0x7f == 2 { 0x14 } else { 0x34 };
+    let mut pointer = (config[pointer_offset] & 0xfc) as usize;
+    // the list can't be longer than this, unless it loops
+    let mut remaining = 48;
+    while pointer != 0 && remaining > 0 {
+        let id = match config.get(pointer) {
+            Some(id) => *id,
+            None => break,
+        };
+
+        match id {
+            CAP_ID_POWER_MANAGEMENT => {
+                if let Some(pmc) = read_u16(config, pointer + 2) {
+                    let states = ["D0", "D1", "D2", "D3hot", "D3cold"];
+                    caps.power_management = Some(PowerManagementCapability {
+                        d1: pmc & (1 << 9) != 0,
+                        d2: pmc & (1 << 10) != 0,
+                        pme_from: states
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| pmc & (1 << (11 + i)) != 0)
+                            .map(|(_, s)| *s)
+                            .collect(),
+                    });
+                }
+            }
+            CAP_ID_EXPRESS => {
+                if let Some(link_cap) = read_u32(config, pointer + 0x0c) {
+                    caps.link = Some(LinkCapability {
+                        max_speed: match link_cap & 0xf {
+                            1 => Some("2.5 GT/s"),
+                            2 => Some("5 GT/s"),
+                            3 => Some("8 GT/s"),
+                            4 => Some("16 GT/s"),
+                            5 => Some("32 GT/s"),
+                            6 => Some("64 GT/s"),
+                            _ => None,
+                        },
+                        max_width: ((link_cap >> 4) & 0x3f) as u8,
+                        aspm_l0s: link_cap & (1 << 10) != 0,
+                        aspm_l1: link_cap & (1 << 11) != 0,
+                        clock_pm: link_cap & (1 << 18) != 0,
+                    });
+                }
+            }
+            _ => (),
+        }
+
+        pointer = match config.get(pointer + 1) {
+            Some(next) => (next & 0xfc) as usize,
+            None => break,
+        };
+        remaining -= 1;
+    }
+
+    if caps.link.is_some() {
+        caps.l1_substates = parse_l1_substates(config);
+    }
+
+    Ok(caps)
+}
+
+/// Looks for the L1 PM Substates capability in the extended configuration
+/// space, which only PCI Express devices have.
+fn parse_l1_substates(config: &[u8]) -> Option<L1SubstatesCapability> {
+    let mut pointer = EXTENDED_START;
+    let mut remaining = (4096 - EXTENDED_START) / 8;
+    while pointer >= EXTENDED_START && remaining > 0 {
+        let header = read_u32(config, pointer)?;
+        if header == 0 || header == 0xffff_ffff {
+            return None;
+        }
+
+        if header & 0xffff == EXT_CAP_ID_L1_SUBSTATES as u32 {
+            let cap = read_u32(config, pointer + 4)?;
+            return Some(L1SubstatesCapability {
+                pci_pm_l1_2: cap & 0x1 != 0,
+                pci_pm_l1_1: cap & 0x2 != 0,
+                aspm_l1_2: cap & 0x4 != 0,
+                aspm_l1_1: cap & 0x8 != 0,
+            });
+        }
+
+        pointer = ((header >> 20) & 0xffc) as usize;
+        remaining -= 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Intel NVMe drive behind a root port, as dumped by `lspci -xxxx`:
+    /// PM at 0x40, MSI at 0x50, Express at 0x70, then AER and L1SS.
+    const NVME_DUMP: &str = "
+        00: 86 80 f3 f1 06 04 10 00 03 02 08 01 00 00 00 00
+        10: 04 00 30 a1 00 00 00 00 00 00 00 00 00 00 00 00
+        20: 00 00 00 00 00 00 00 00 00 00 00 00 86 80 0a 39
+        30: 00 00 00 00 40 00 00 00 00 00 00 00 ff 01 00 00
+        40: 01 50 03 c8 08 00 00 00 00 00 00 00 00 00 00 00
+        50: 05 70 80 00 00 00 00 00 00 00 00 00 00 00 00 00
+        70: 10 00 02 00 c1 8f 00 10 10 29 10 00 43 08 04 00
+        100: 01 00 01 15 00 00 00 00 00 00 00 00 00 00 00 00
+        150: 1e 00 01 00 1f 00 00 00 00 00 00 00 00 00 00 00
+    ";
+
+    /// Turns a dump into a configuration space of `size` bytes, the lines
+    /// that aren't in the dump being zeroes.
+    fn config_from_dump(dump: &str, size: usize) -> Vec<u8> {
+        let mut config = vec![0; 4096];
+        for line in dump.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let mut parts = line.splitn(2, ':');
+            let offset = usize::from_str_radix(parts.next().unwrap(), 16).unwrap();
+            for (i, byte) in parts.next().unwrap().split_whitespace().enumerate() {
+                config[offset + i] = u8::from_str_radix(byte, 16).unwrap();
+            }
+        }
+        config.truncate(size);
+
+        config
+    }
+
+    fn write_u32(config: &mut [u8], offset: usize, value: u32) {
+        config[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn parse_full_config() {
+        let config = config_from_dump(NVME_DUMP, 4096);
+        assert!(!needs_privileges(&config));
+
+        let caps = parse_capabilities(&config).unwrap();
+
+        let pm = caps.power_management.unwrap();
+        assert!(!pm.d1);
+        assert!(!pm.d2);
+        assert_eq!(pm.pme_from, ["D0", "D3hot", "D3cold"]);
+
+        let link = caps.link.unwrap();
+        assert_eq!(link.max_speed, Some("8 GT/s"));
+        assert_eq!(link.max_width, 4);
+        assert_eq!(link.get_aspm_description(), "L1");
+        assert!(link.clock_pm);
+
+        let l1ss = caps.l1_substates.unwrap();
+        assert_eq!(
+            l1ss.get_description(),
+            "ASPM L1.1 ASPM L1.2 PCI-PM L1.1 PCI-PM L1.2"
+        );
+    }
+
+    #[test]
+    fn parse_unprivileged_config() {
+        let config = config_from_dump(NVME_DUMP, HEADER_SIZE);
+
+        assert!(needs_privileges(&config));
+        assert!(parse_capabilities(&config).is_err());
+        assert!(parse_capabilities(&config[..HEADER_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn parse_config_without_capabilities() {
+        let mut config = config_from_dump(NVME_DUMP, HEADER_SIZE);
+        // clear the capability list bit of the status register
+        config[0x06] = 0;
+
+        assert!(!needs_privileges(&config));
+        let caps = parse_capabilities(&config).unwrap();
+        assert!(caps.power_management.is_none());
+        assert!(caps.link.is_none());
+        assert!(caps.l1_substates.is_none());
+    }
+
+    #[test]
+    fn parse_conventional_config() {
+        let mut config = config_from_dump(NVME_DUMP, 256);
+        // the MSI capability ends the list, before the Express one
+        config[0x51] = 0;
+
+        let caps = parse_capabilities(&config).unwrap();
+        assert!(caps.power_management.is_some());
+        assert!(caps.link.is_none());
+        assert!(caps.l1_substates.is_none());
+    }
+
+    #[test]
+    fn parse_looping_capabilities() {
+        let mut config = config_from_dump(NVME_DUMP, 256);
+        // the Express capability points back to the PM one
+        config[0x71] = 0x40;
+
+        let caps = parse_capabilities(&config).unwrap();
+        assert!(caps.power_management.is_some());
+        assert!(caps.link.is_some());
+    }
+
+    #[test]
+    fn parse_power_management() {
+        let table = [
+            (0x0003, false, false, vec![]),
+            (0x0603, true, true, vec![]),
+            (0xc803, false, false, vec!["D0", "D3hot", "D3cold"]),
+            (
+                0xfe03,
+                true,
+                true,
+                vec!["D0", "D1", "D2", "D3hot", "D3cold"],
+            ),
+        ];
+
+        for (pmc, d1, d2, pme_from) in table.iter() {
+            let mut config = config_from_dump(NVME_DUMP, 4096);
+            config[0x42..0x44].copy_from_slice(&(*pmc as u16).to_le_bytes());
+
+            let pm = parse_capabilities(&config)
+                .unwrap()
+                .power_management
+                .unwrap();
+            assert_eq!(pm.d1, *d1, "{:04x}", pmc);
+            assert_eq!(pm.d2, *d2, "{:04x}", pmc);
+            assert_eq!(&pm.pme_from, pme_from, "{:04x}", pmc);
+        }
+    }
+
+    #[test]
+    fn parse_link_capability() {
+        let table = [
+            (0x0000_0011, Some("2.5 GT/s"), 1, "Not supported", false),
+            (0x0000_0422, Some("5 GT/s"), 2, "L0s", false),
+            (0x0004_0843, Some("8 GT/s"), 4, "L1", true),
+            (0x0000_0c84, Some("16 GT/s"), 8, "L0s L1", false),
+            (0x0000_0c07, None, 0, "L0s L1", false),
+        ];
+
+        for (link_cap, speed, width, aspm, clock_pm) in table.iter() {
+            let mut config = config_from_dump(NVME_DUMP, 4096);
+            write_u32(&mut config, 0x7c, *link_cap);
+
+            let link = parse_capabilities(&config).unwrap().link.unwrap();
+            assert_eq!(link.max_speed, *speed, "{:08x}", link_cap);
+            assert_eq!(link.max_width, *width, "{:08x}", link_cap);
+            assert_eq!(link.get_aspm_description(), *aspm, "{:08x}", link_cap);
+            assert_eq!(link.clock_pm, *clock_pm, "{:08x}", link_cap);
+        }
+    }
+
+    #[test]
+    fn parse_l1_substates_capability() {
+        let table = [
+            (0x0000_0010, "None"),
+            (0x0000_0011, "PCI-PM L1.2"),
+            (0x0000_0018, "ASPM L1.1"),
+            (0x0000_001c, "ASPM L1.1 ASPM L1.2"),
+        ];
+
+        for (cap, description) in table.iter() {
+            let mut config = config_from_dump(NVME_DUMP, 4096);
+            write_u32(&mut config, 0x154, *cap);
+
+            let l1ss = parse_l1_substates(&config).unwrap();
+            assert_eq!(l1ss.get_description(), *description, "{:08x}", cap);
+        }
+    }
+
+    #[test]
+    fn parse_missing_l1_substates() {
+        let mut config = config_from_dump(NVME_DUMP, 4096);
+        // AER is the last extended capability
+        write_u32(&mut config, 0x100, 0x0001_0001);
+        assert!(parse_l1_substates(&config).is_none());
+
+        // no extended configuration space at all
+        let config = config_from_dump(NVME_DUMP, 256);
+        assert!(parse_l1_substates(&config).is_none());
+        assert!(parse_capabilities(&config).unwrap().l1_substates.is_none());
+    }
+}
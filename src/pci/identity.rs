@@ -0,0 +1,139 @@
+use anyhow::*;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Identity of a PCI device that stays the same across reboots.
+///
+/// The slot (`domain:bus:slot.func`) pins the device to its location, while the
+/// vendor/device and subsystem IDs describe the device and the board it is on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PciIdentity {
+    pub slot: String,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub subsystem_vendor_id: Option<u16>,
+    pub subsystem_device_id: Option<u16>,
+}
+
+impl Display for PciIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} {:04x}:{:04x}",
+            self.slot, self.vendor_id, self.device_id
+        )?;
+        if let (Some(vendor), Some(device)) = (self.subsystem_vendor_id, self.subsystem_device_id) {
+            write!(f, " ({:04x}:{:04x})", vendor, device)?;
+        }
+        Ok(())
+    }
+}
+
+/// A pattern over [`PciIdentity`]: every field that is set must be equal.
+///
+/// It is written as a comma-separated list of `key=value` pairs, for example
+/// `vendor=8086,device=a0ed` or `slot=0000:00:14.0`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PciMatcher {
+    pub slot: Option<String>,
+    pub vendor_id: Option<u16>,
+    pub device_id: Option<u16>,
+    pub subsystem_vendor_id: Option<u16>,
+    pub subsystem_device_id: Option<u16>,
+}
+
+impl PciMatcher {
+    pub fn matches(&self, identity: &PciIdentity) -> bool {
+        if let Some(slot) = &self.slot {
+            if *slot != identity.slot {
+                return false;
+            }
+        }
+        if let Some(vendor_id) = self.vendor_id {
+            if vendor_id != identity.vendor_id {
+                return false;
+            }
+        }
+        if let Some(device_id) = self.device_id {
+            if device_id != identity.device_id {
+                return false;
+            }
+        }
+        if self.subsystem_vendor_id.is_some()
+            && self.subsystem_vendor_id != identity.subsystem_vendor_id
+        {
+            return false;
+        }
+        if self.subsystem_device_id.is_some()
+            && self.subsystem_device_id != identity.subsystem_device_id
+        {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl From<&PciIdentity> for PciMatcher {
+    /// Builds a matcher for exactly this device.
+    fn from(identity: &PciIdentity) -> Self {
+        PciMatcher {
+            slot: Some(identity.slot.clone()),
+            vendor_id: Some(identity.vendor_id),
+            device_id: Some(identity.device_id),
+            subsystem_vendor_id: identity.subsystem_vendor_id,
+            subsystem_device_id: identity.subsystem_device_id,
+        }
+    }
+}
+
+impl Display for PciMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut fields = Vec::new();
+        if let Some(slot) = &self.slot {
+            fields.push(format!("slot={}", slot));
+        }
+        if let Some(vendor_id) = self.vendor_id {
+            fields.push(format!("vendor={:04x}", vendor_id));
+        }
+        if let Some(device_id) = self.device_id {
+            fields.push(format!("device={:04x}", device_id));
+        }
+        if let Some(subsystem_vendor_id) = self.subsystem_vendor_id {
+            fields.push(format!("subvendor={:04x}", subsystem_vendor_id));
+        }
+        if let Some(subsystem_device_id) = self.subsystem_device_id {
+            fields.push(format!("subdevice={:04x}", subsystem_device_id));
+        }
+
+        f.write_str(&fields.join(","))
+    }
+}
+
+impl FromStr for PciMatcher {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut matcher = PciMatcher::default();
+
+        for field in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let mut parts = field.splitn(2, '=');
+            let key = parts.next().unwrap_or_default().trim();
+            let value = parts
+                .next()
+                .ok_or_else(|| anyhow!("missing value for '{}' in pci matcher", key))?
+                .trim();
+
+            match key {
+                "slot" => matcher.slot = Some(value.to_owned()),
+                "vendor" => matcher.vendor_id = Some(u16::from_str_radix(value, 16)?),
+                "device" => matcher.device_id = Some(u16::from_str_radix(value, 16)?),
+                "subvendor" => matcher.subsystem_vendor_id = Some(u16::from_str_radix(value, 16)?),
+                "subdevice" => matcher.subsystem_device_id = Some(u16::from_str_radix(value, 16)?),
+                _ => bail!("unknown pci matcher key '{}'", key),
+            }
+        }
+
+        Ok(matcher)
+    }
+}
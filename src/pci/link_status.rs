@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::Path;
+
+/// Speed and width of the PCIe link of a device, as trained and as supported.
+#[derive(Clone, Debug)]
+pub struct LinkStatus {
+    current_speed: Option<String>,
+    max_speed: Option<String>,
+    current_width: Option<u8>,
+    max_width: Option<u8>,
+}
+
+impl LinkStatus {
+    /// Reads the link attributes of a device, if it is a PCIe device.
+    pub(crate) fn read(device_path: &Path) -> Option<LinkStatus> {
+        let read = |name: &str| {
+            fs::read_to_string(device_path.join(name))
+                .ok()
+                .map(|s| s.trim().to_owned())
+        };
+
+        let status = LinkStatus {
+            current_speed: read("current_link_speed"),
+            max_speed: read("max_link_speed"),
+            current_width: read("current_link_width").and_then(|w| w.parse().ok()),
+            max_width: read("max_link_width").and_then(|w| w.parse().ok()),
+        };
+
+        if status.max_speed.is_none() && status.max_width.is_none() {
+            None
+        } else {
+            Some(status)
+        }
+    }
+
+    pub fn current_speed(&self) -> Option<&str> {
+        self.current_speed.as_deref()
+    }
+
+    pub fn max_speed(&self) -> Option<&str> {
+        self.max_speed.as_deref()
+    }
+
+    pub fn current_width(&self) -> Option<u8> {
+        self.current_width
+    }
+
+    pub fn max_width(&self) -> Option<u8> {
+        self.max_width
+    }
+
+    /// Whether the link is narrower than what both ends support.
+    ///
+    /// `upstream` is the link status of the port the device is connected to,
+    /// which can be the one limiting the link. The speed isn't compared, as
+    /// many devices (GPUs, NVMe drives...) lower it on their own when idle.
+    pub fn is_downtrained(&self, upstream: Option<&LinkStatus>) -> bool {
+        let max_width = min_option(self.max_width, upstream.and_then(|u| u.max_width));
+
+        match (self.current_width, max_width) {
+            // a width of 0 means the link is down
+            (Some(current), Some(max)) => current != 0 && current < max,
+            _ => false,
+        }
+    }
+
+    /// Whether the link runs at the highest speed of the device, when that is
+    /// faster than the lowest speed of PCIe.
+    pub fn is_at_max_speed(&self) -> bool {
+        match (
+            self.current_speed.as_deref().and_then(parse_speed),
+            self.max_speed.as_deref().and_then(parse_speed),
+        ) {
+            (Some(current), Some(max)) => max > 2.5 && current >= max,
+            _ => false,
+        }
+    }
+}
+
+/// Parses a speed like "8.0 GT/s PCIe" into GT/s.
+fn parse_speed(speed: &str) -> Option<f32> {
+    speed.split_whitespace().next()?.parse().ok()
+}
+
+fn min_option<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b < a { b } else { a }),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(current: (&str, u8), max: (&str, u8)) -> LinkStatus {
+        LinkStatus {
+            current_speed: Some(current.0.to_owned()),
+            max_speed: Some(max.0.to_owned()),
+            current_width: Some(current.1),
+            max_width: Some(max.1),
+        }
+    }
+
+    #[test]
+    fn parse_speeds() {
+        let table = [
+            ("2.5 GT/s PCIe", Some(2.5)),
+            ("8.0 GT/s PCIe", Some(8.0)),
+            ("16 GT/s", Some(16.0)),
+            ("Unknown", None),
+            ("", None),
+        ];
+
+        for (speed, expected) in table.iter() {
+            assert_eq!(parse_speed(speed), *expected, "{}", speed);
+        }
+    }
+
+    #[test]
+    fn downtrained() {
+        let table = [
+            // full width, at a lower speed
+            (("2.5 GT/s PCIe", 4), ("8.0 GT/s PCIe", 4), None, false),
+            (("8.0 GT/s PCIe", 2), ("8.0 GT/s PCIe", 4), None, true),
+            // the link is down
+            (("Unknown", 0), ("8.0 GT/s PCIe", 4), None, false),
+            // the port only has two lanes
+            (("8.0 GT/s PCIe", 2), ("8.0 GT/s PCIe", 4), Some(2), false),
+            (("8.0 GT/s PCIe", 1), ("8.0 GT/s PCIe", 4), Some(2), true),
+        ];
+
+        for (current, max, upstream_width, expected) in table.iter() {
+            let link = status(*current, *max);
+            let upstream =
+                upstream_width.map(|w| status(("8.0 GT/s PCIe", w), ("8.0 GT/s PCIe", w)));
+
+            assert_eq!(
+                link.is_downtrained(upstream.as_ref()),
+                *expected,
+                "{:?} behind {:?}",
+                link,
+                upstream
+            );
+        }
+    }
+
+    #[test]
+    fn at_max_speed() {
+        let table = [
+            ("8.0 GT/s PCIe", "8.0 GT/s PCIe", true),
+            ("2.5 GT/s PCIe", "8.0 GT/s PCIe", false),
+            // nothing to lower the speed to
+            ("2.5 GT/s PCIe", "2.5 GT/s PCIe", false),
+            ("Unknown", "8.0 GT/s PCIe", false),
+        ];
+
+        for (current, max, expected) in table.iter() {
+            let link = status((current, 4), (max, 4));
+            assert_eq!(link.is_at_max_speed(), *expected, "{} of {}", current, max);
+        }
+    }
+}
@@ -1,14 +1,27 @@
+mod aspm;
+mod config;
+mod identity;
+mod link_status;
+
+pub use aspm::{read_policy, AspmPolicy, LinkState, PciLink};
+pub use config::{
+    L1SubstatesCapability, LinkCapability, PciCapabilities, PowerManagementCapability,
+};
+pub use identity::{PciIdentity, PciMatcher};
+pub use link_status::LinkStatus;
+
 use crate::db::{parse_db, Db};
-use crate::fs::write_string_privileged;
+use crate::fs::{read_privileged, write_string_privileged};
 use anyhow::*;
 use log::*;
 use std::fmt::Display;
 use std::fs;
 use std::path::PathBuf;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PciDevice {
     id: String,
+    identity: PciIdentity,
     device_path: PathBuf,
     vendor_id: Option<u16>,
     device_id: Option<u16>,
@@ -17,11 +30,24 @@ pub struct PciDevice {
     autosuspend: bool,
     delay: u64,
     kind: PciKind,
+    driver: Option<String>,
+    module: Option<String>,
+    runtime_pm_supported: bool,
+    link: Option<PciLink>,
+    d3cold_allowed: Option<bool>,
+    allow_wakeup: Option<bool>,
+    /// The wakeup setting when it was read, toggling an ACPI entry changes it.
+    read_allow_wakeup: Option<bool>,
+    upstream_link_status: Option<LinkStatus>,
 }
 
 impl PciDevice {
     fn from(device_path: PathBuf, id: String) -> PciDevice {
         PciDevice {
+            identity: PciIdentity {
+                slot: id.clone(),
+                ..PciIdentity::default()
+            },
             id,
             device_path,
             vendor_id: None,
@@ -31,6 +57,14 @@ impl PciDevice {
             autosuspend: false,
             delay: 0,
             kind: PciKind::default(),
+            driver: None,
+            module: None,
+            runtime_pm_supported: false,
+            link: None,
+            d3cold_allowed: None,
+            allow_wakeup: None,
+            read_allow_wakeup: None,
+            upstream_link_status: None,
         }
     }
 
@@ -38,6 +72,14 @@ impl PciDevice {
         &self.id
     }
 
+    pub fn identity(&self) -> &PciIdentity {
+        &self.identity
+    }
+
+    pub fn matches(&self, matcher: &PciMatcher) -> bool {
+        matcher.matches(&self.identity)
+    }
+
     pub fn get_name(&self) -> String {
         let mut desc = String::new();
         if let Some(device) = self.db_device_name.as_ref() {
@@ -75,6 +117,25 @@ impl PciDevice {
         }
     }
 
+    /// The name of the driver bound to the device.
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
+
+    /// The kernel module providing the driver, unless it is built-in.
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    pub fn get_driver_description(&self) -> String {
+        match (&self.driver, &self.module) {
+            (Some(driver), Some(module)) if driver == module => driver.clone(),
+            (Some(driver), Some(module)) => format!("{} ({})", driver, module),
+            (Some(driver), None) => format!("{} (built-in)", driver),
+            (None, _) => "No driver".to_owned(),
+        }
+    }
+
     pub fn kind(&self) -> &PciKind {
         &self.kind
     }
@@ -87,6 +148,23 @@ impl PciDevice {
         self.delay
     }
 
+    /// Explains why enabling autosuspend would have no effect on this device.
+    ///
+    /// The PCI core keeps devices without a driver in D0, and runtime PM has to
+    /// be enabled for the device at all.
+    pub fn autosuspend_unsupported_reason(&self) -> Option<String> {
+        if self.driver.is_none() {
+            Some("Autosuspend has no effect: no driver is bound to this device.".to_owned())
+        } else if !self.runtime_pm_supported {
+            Some(
+                "Autosuspend has no effect: runtime power management is disabled for this device."
+                    .to_owned(),
+            )
+        } else {
+            None
+        }
+    }
+
     pub fn set_autosuspend(&mut self, autosuspend: bool) {
         self.autosuspend = autosuspend;
     }
@@ -95,6 +173,167 @@ impl PciDevice {
         self.delay = delay;
     }
 
+    /// The current power state of the device (`D0`, `D3hot`, `D3cold`...).
+    ///
+    /// This is read from the device every time, so that it can be shown live.
+    pub fn power_state(&self) -> Option<String> {
+        fs::read_to_string(self.device_path.join("power_state"))
+            .ok()
+            .map(|s| s.trim().to_owned())
+    }
+
+    /// Reads what the device supports from its configuration space.
+    ///
+    /// Most capabilities are past the part of the configuration space that
+    /// unprivileged users can read: this is `None` when they aren't readable,
+    /// see [`capabilities_privileged`](Self::capabilities_privileged).
+    pub fn capabilities(&self) -> Result<Option<PciCapabilities>> {
+        let config = fs::read(self.device_path.join("config"))?;
+        if config::needs_privileges(&config) {
+            return Ok(None);
+        }
+
+        config::parse_capabilities(&config).map(Some)
+    }
+
+    /// Reads the capabilities as root, which asks the user to authenticate.
+    pub async fn capabilities_privileged(&self) -> Result<PciCapabilities> {
+        let config = read_privileged(&self.device_path.join("config")).await?;
+
+        config::parse_capabilities(&config)
+    }
+
+    /// Whether the device can be put in D3cold, where it is completely powered off.
+    pub fn d3cold_allowed(&self) -> Option<bool> {
+        self.d3cold_allowed
+    }
+
+    pub fn set_d3cold_allowed(&mut self, allowed: bool) -> Result<()> {
+        if self.d3cold_allowed.is_none() {
+            bail!("device doesn't support controlling D3cold");
+        }
+        self.d3cold_allowed = Some(allowed);
+
+        Ok(())
+    }
+
+    /// Whether the device can wake the system up, if it supports it.
+    pub fn allow_wakeup(&self) -> Option<bool> {
+        self.allow_wakeup
+    }
+
+    pub fn set_allow_wakeup(&mut self, allow_wakeup: bool) -> Result<()> {
+        if self.allow_wakeup.is_none() {
+            bail!("device doesn't support wakeup");
+        }
+        self.allow_wakeup = Some(allow_wakeup);
+
+        Ok(())
+    }
+
+    /// The speed and width of the PCIe link of this device, as they are now.
+    pub fn link_status(&self) -> Option<LinkStatus> {
+        LinkStatus::read(&self.device_path)
+    }
+
+    /// Explains what is wrong with the PCIe link of this device, if anything.
+    ///
+    /// A link narrower than what both ends support costs performance, and a
+    /// link kept at full speed while the device is suspended costs power.
+    pub fn link_warning(&self) -> Option<String> {
+        let status = self.link_status()?;
+        let runtime_status = fs::read_to_string(self.device_path.join("power/runtime_status"));
+        let suspended = matches!(runtime_status.as_deref().map(str::trim), Ok("suspended"));
+
+        if status.is_downtrained(self.upstream_link_status.as_ref()) {
+            Some("The link is narrower than what the device and its port support.".to_owned())
+        } else if suspended && status.is_at_max_speed() {
+            Some("The link runs at full speed while the device is idle.".to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// The ASPM settings of the PCIe link of this device, if it can be controlled.
+    pub fn link(&self) -> Option<&PciLink> {
+        self.link.as_ref()
+    }
+
+    /// Reads the ASPM settings of the link again, keeping the ones changed
+    /// since they were read.
+    pub fn refresh_link(&mut self) {
+        if let Some(link) = &mut self.link {
+            link.refresh();
+        }
+    }
+
+    pub fn set_link_state(&mut self, state: LinkState, enabled: bool) -> Result<()> {
+        match &mut self.link {
+            Some(link) => link.set(state, enabled),
+            None => bail!("device doesn't have a controllable PCIe link"),
+        }
+    }
+
+    /// The settings of this device, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let control = if self.autosuspend { "auto" } else { "on" };
+
+        let mut settings = vec![
+            ("control".to_owned(), control.to_owned()),
+            ("autosuspend_delay_ms".to_owned(), self.delay.to_string()),
+        ];
+        if let Some(allowed) = self.d3cold_allowed {
+            let value = if allowed { "1" } else { "0" };
+            settings.push(("d3cold_allowed".to_owned(), value.to_owned()));
+        }
+        if let Some(wakeup) = self.allow_wakeup {
+            let wakeup = if wakeup { "enabled" } else { "disabled" };
+            settings.push(("wakeup".to_owned(), wakeup.to_owned()));
+        }
+        if let Some(link) = &self.link {
+            for (state, enabled) in link.states() {
+                let value = if *enabled { "1" } else { "0" };
+                settings.push((format!("link/{}", state.attribute()), value.to_owned()));
+            }
+        }
+
+        settings
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "control" => match value {
+                "auto" => self.autosuspend = true,
+                "on" => self.autosuspend = false,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            "autosuspend_delay_ms" => self.delay = value.parse()?,
+            "d3cold_allowed" => match value {
+                "1" => self.set_d3cold_allowed(true)?,
+                "0" => self.set_d3cold_allowed(false)?,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            "wakeup" => match value {
+                "enabled" => self.set_allow_wakeup(true)?,
+                "disabled" => self.set_allow_wakeup(false)?,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            _ if key.starts_with("link/") => {
+                let state = LinkState::from_attribute(&key[5..])
+                    .ok_or_else(|| anyhow!("unknown pci setting '{}'", key))?;
+                match value {
+                    "1" => self.set_link_state(state, true)?,
+                    "0" => self.set_link_state(state, false)?,
+                    v => bail!("invalid value '{}' for '{}'", v, key),
+                }
+            }
+            _ => bail!("unknown pci setting '{}'", key),
+        }
+
+        Ok(())
+    }
+
     pub async fn save(&self) -> Result<()> {
         let control_path = self.device_path.join("power/control");
         let autosuspend_delay_path = self.device_path.join("power/autosuspend_delay_ms");
@@ -116,6 +355,25 @@ impl PciDevice {
         write_string_privileged(&control_path, control_text).await?;
         write_string_privileged(&autosuspend_delay_path, autosuspend_delay_text).await?;
 
+        if let Some(allowed) = self.d3cold_allowed {
+            let text = if allowed { "1" } else { "0" };
+            write_string_privileged(&self.device_path.join("d3cold_allowed"), text.to_owned())
+                .await?;
+        }
+
+        if let (Some(wakeup), true) = (
+            self.allow_wakeup,
+            self.allow_wakeup != self.read_allow_wakeup,
+        ) {
+            let text = if wakeup { "enabled" } else { "disabled" };
+            write_string_privileged(&self.device_path.join("power/wakeup"), text.to_owned())
+                .await?;
+        }
+
+        if let Some(link) = &self.link {
+            link.save().await?;
+        }
+
         Ok(())
     }
 }
@@ -166,15 +424,6 @@ impl Display for PciKind {
     }
 }
 
-macro_rules! match_warn {
-    ($content:expr, $format:expr$(=> $add:expr)? , $bind:ident => $func:expr) => {
-        match $content {
-            Err(e) => {warn!($format, e) $(; $add)?},
-            Ok($bind) => $func,
-        }
-    };
-}
-
 pub fn list_devices() -> Result<Vec<PciDevice>> {
     let db = parse_db("/usr/share/hwdata/pci.ids")
         .map_err(|e| {
@@ -221,28 +470,49 @@ fn make_device(path: PathBuf, pci_db: Option<&Db>) -> Result<PciDevice> {
     };
     let vendor_path = path.join("vendor");
     let device_path = path.join("device");
+    let subsystem_vendor_path = path.join("subsystem_vendor");
+    let subsystem_device_path = path.join("subsystem_device");
     let class_path = path.join("class");
+    let driver_path = path.join("driver");
     let control = path.join("power/control");
     let autosuspend_delay = path.join("power/autosuspend_delay_ms");
+    let runtime_status = path.join("power/runtime_status");
+    let d3cold_allowed = path.join("d3cold_allowed");
+    let wakeup = path.join("power/wakeup");
 
+    let link = PciLink::read(&path);
+    let upstream_link_status = path.parent().and_then(LinkStatus::read);
     let mut pci_device = PciDevice::from(path, id);
+    pci_device.link = link;
+    pci_device.upstream_link_status = upstream_link_status;
 
     if let Ok(vendor) = fs::read_to_string(&vendor_path) {
         let vendor_id = u16::from_str_radix(&vendor.trim()[2..], 16)?;
         pci_device.vendor_id = Some(vendor_id);
+        if let Ok(device) = fs::read_to_string(&device_path) {
+            pci_device.device_id = Some(u16::from_str_radix(&device.trim()[2..], 16)?);
+        }
         if let Some(vendor) = pci_db.and_then(|db| db.vendors.get(&vendor_id)) {
             pci_device.db_vendor_name = Some(vendor.name.trim().to_string());
 
-            if let Ok(device) = fs::read_to_string(&device_path) {
-                let device_id = u16::from_str_radix(&device.trim()[2..], 16)?;
-                pci_device.device_id = Some(device_id);
-                if let Some(device) = vendor.devices.get(&device_id) {
-                    pci_device.db_device_name = Some(device.trim().to_string());
-                }
+            if let Some(device) = pci_device
+                .device_id
+                .and_then(|device_id| vendor.devices.get(&device_id))
+            {
+                pci_device.db_device_name = Some(device.trim().to_string());
             }
         }
     }
 
+    pci_device.identity.vendor_id = pci_device.vendor_id.unwrap_or_default();
+    pci_device.identity.device_id = pci_device.device_id.unwrap_or_default();
+    if let Ok(vendor) = fs::read_to_string(&subsystem_vendor_path) {
+        pci_device.identity.subsystem_vendor_id = u16::from_str_radix(&vendor.trim()[2..], 16).ok();
+    }
+    if let Ok(device) = fs::read_to_string(&subsystem_device_path) {
+        pci_device.identity.subsystem_device_id = u16::from_str_radix(&device.trim()[2..], 16).ok();
+    }
+
     if let Ok(class_str) = fs::read_to_string(&class_path) {
         let class_str = class_str.trim();
         if let Ok(class_id) = u16::from_str_radix(&class_str[2..=3], 16) {
@@ -260,6 +530,18 @@ fn make_device(path: PathBuf, pci_db: Option<&Db>) -> Result<PciDevice> {
         }
     }
 
+    if let Ok(driver) = fs::read_link(&driver_path) {
+        pci_device.driver = driver
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+
+        if let Ok(module) = fs::read_link(driver_path.join("module")) {
+            pci_device.module = module
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+        }
+    }
+
     let autosuspend = match fs::read_to_string(&control)?.trim() {
         "on" => false,
         "auto" => true,
@@ -267,6 +549,24 @@ fn make_device(path: PathBuf, pci_db: Option<&Db>) -> Result<PciDevice> {
     };
     pci_device.autosuspend = autosuspend;
 
+    if let Ok(status) = fs::read_to_string(&runtime_status) {
+        pci_device.runtime_pm_supported = status.trim() != "unsupported";
+    }
+
+    if let Ok(allowed) = fs::read_to_string(&d3cold_allowed) {
+        pci_device.d3cold_allowed = Some(allowed.trim() == "1");
+    }
+
+    // empty when the device can't wake the system up
+    if let Ok(wakeup) = fs::read_to_string(&wakeup) {
+        match wakeup.trim() {
+            "enabled" => pci_device.allow_wakeup = Some(true),
+            "disabled" => pci_device.allow_wakeup = Some(false),
+            _ => (),
+        }
+        pci_device.read_allow_wakeup = pci_device.allow_wakeup;
+    }
+
     if let Ok(delay) = fs::read_to_string(&autosuspend_delay) {
         match delay.trim().parse::<i64>()? {
             -1 => pci_device.autosuspend = false,
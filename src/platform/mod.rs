@@ -0,0 +1,79 @@
+use crate::fs::write_string_privileged;
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::Path;
+
+const PROFILE_PATH: &str = "/sys/firmware/acpi/platform_profile";
+const CHOICES_PATH: &str = "/sys/firmware/acpi/platform_profile_choices";
+
+/// The ACPI platform profile, which lets the firmware trade performance for
+/// power and fan noise.
+#[derive(Clone, Debug)]
+pub struct PlatformProfile {
+    current: String,
+    choices: Vec<String>,
+    /// The profile when it was read.
+    read: String,
+}
+
+impl PlatformProfile {
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn choices(&self) -> &[String] {
+        &self.choices
+    }
+
+    pub fn set(&mut self, profile: &str) -> Result<()> {
+        if !self.choices.iter().any(|c| c == profile) {
+            bail!("unknown platform profile '{}'", profile);
+        }
+
+        self.current = profile.to_owned();
+
+        Ok(())
+    }
+
+    pub fn get_description(profile: &str) -> String {
+        match profile {
+            "low-power" => "Low power".to_owned(),
+            "cool" => "Cool".to_owned(),
+            "quiet" => "Quiet".to_owned(),
+            "balanced" => "Balanced".to_owned(),
+            "balanced-performance" => "Balanced performance".to_owned(),
+            "performance" => "Performance".to_owned(),
+            p => p.to_owned(),
+        }
+    }
+
+    /// Writes the profile if it was changed since it was read, returning
+    /// whether it was written.
+    pub async fn save(&self) -> Result<bool> {
+        if self.current == self.read {
+            return Ok(false);
+        }
+
+        trace!("saving '{}' with {}", PROFILE_PATH, self.current);
+
+        write_string_privileged(Path::new(PROFILE_PATH), self.current.clone()).await?;
+
+        Ok(true)
+    }
+}
+
+/// Reads the platform profile, if the firmware has one.
+pub fn read_platform_profile() -> Result<PlatformProfile> {
+    let current = fs::read_to_string(PROFILE_PATH)?.trim().to_owned();
+    let choices = fs::read_to_string(CHOICES_PATH)?
+        .split_whitespace()
+        .map(|c| c.to_owned())
+        .collect();
+
+    Ok(PlatformProfile {
+        read: current.clone(),
+        current,
+        choices,
+    })
+}
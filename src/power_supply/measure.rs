@@ -0,0 +1,137 @@
+use super::{ac_online, Battery};
+use anyhow::*;
+use log::*;
+use std::fmt::Write;
+use std::fs;
+use std::time::Instant;
+
+/// One reading of the power drawn from the batteries.
+#[derive(Clone, Debug)]
+pub struct PowerSample {
+    /// Seconds since the start of the measurement.
+    pub time: f64,
+    pub watts: f64,
+}
+
+/// The samples taken over one measurement.
+#[derive(Clone, Debug, Default)]
+pub struct Measurement {
+    samples: Vec<PowerSample>,
+}
+
+impl Measurement {
+    pub fn samples(&self) -> &[PowerSample] {
+        &self.samples
+    }
+
+    pub fn push(&mut self, sample: PowerSample) {
+        self.samples.push(sample);
+    }
+
+    /// The average power draw, in watts.
+    pub fn mean(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        Some(self.samples.iter().map(|s| s.watts).sum::<f64>() / self.samples.len() as f64)
+    }
+
+    /// The sample variance of the power draw, in W².
+    pub fn variance(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+
+        let mean = self.mean()?;
+        let squares: f64 = self.samples.iter().map(|s| (s.watts - mean).powi(2)).sum();
+
+        Some(squares / (self.samples.len() - 1) as f64)
+    }
+}
+
+/// Reads the power drawn from the batteries.
+///
+/// `power_now` is used when every battery has it. Otherwise the draw is
+/// computed from how fast `energy_now` goes down, which only changes every
+/// few seconds: a sample is only taken when it does.
+pub struct PowerMeter {
+    batteries: Vec<Battery>,
+    /// The last energy read, and when it changed to that value if known.
+    last_energy: Option<(Option<Instant>, u64)>,
+}
+
+impl PowerMeter {
+    /// Fails unless the computer runs on battery, as the draw of the system
+    /// can't be seen while charging.
+    pub fn new(batteries: &[Battery]) -> Result<PowerMeter> {
+        if batteries.is_empty() {
+            bail!("no battery to measure the power draw from");
+        }
+        if ac_online() == Some(true) {
+            bail!("the computer must be unplugged to measure its power draw");
+        }
+
+        Ok(PowerMeter {
+            batteries: batteries.to_vec(),
+            last_energy: None,
+        })
+    }
+
+    /// Reads the current draw in watts, `None` if there is no new value yet.
+    pub fn read(&mut self) -> Result<Option<f64>> {
+        let mut power = Some(0);
+        let mut energy = Some(0);
+        for b in &self.batteries {
+            let read = |name: &str| {
+                fs::read_to_string(b.path.join(name))
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+            };
+            let status = fs::read_to_string(b.path.join("status")).unwrap_or_default();
+            if status.trim() == "Charging" {
+                bail!("{} started charging", b.get_id());
+            }
+
+            power = power.and_then(|p| read("power_now").filter(|n| *n > 0).map(|n| p + n));
+            energy = energy.and_then(|e| read("energy_now").map(|n| e + n));
+        }
+
+        if let Some(power) = power {
+            return Ok(Some(power as f64 / 1e6));
+        }
+        let energy = match energy {
+            Some(e) => e,
+            None => bail!("the batteries report neither power_now nor energy_now"),
+        };
+
+        let now = Instant::now();
+        let watts = match self.last_energy {
+            Some((_, last)) if last == energy => return Ok(None),
+            Some((Some(time), last)) => {
+                let hours = now.duration_since(time).as_secs_f64() / 3600.0;
+                Some(last.saturating_sub(energy) as f64 / 1e6 / hours)
+            }
+            // the first change only tells when the value is exact
+            _ => None,
+        };
+        trace!("energy changed to {} µWh", energy);
+        let changed_at = self.last_energy.map(|_| now);
+        self.last_energy = Some((changed_at, energy));
+
+        Ok(watts)
+    }
+}
+
+/// The samples of an A/B measurement, as CSV.
+pub fn to_csv(before: &Measurement, after: &Measurement) -> String {
+    let mut csv = String::from("phase,time_s,power_w\n");
+    for (phase, measurement) in &[("before", before), ("after", after)] {
+        for s in measurement.samples() {
+            // writing to a String can't fail
+            let _ = writeln!(csv, "{},{:.1},{:.3}", phase, s.time, s.watts);
+        }
+    }
+
+    csv
+}
@@ -0,0 +1,276 @@
+mod measure;
+mod watch;
+
+pub use measure::{to_csv, Measurement, PowerMeter, PowerSample};
+pub use watch::watch_power_source;
+
+use crate::fs::write_string_privileged;
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+
+const START_THRESHOLD: &str = "charge_control_start_threshold";
+const END_THRESHOLD: &str = "charge_control_end_threshold";
+
+/// A battery, with its charge thresholds if the firmware lets us control them.
+#[derive(Clone, Debug)]
+pub struct Battery {
+    id: String,
+    path: PathBuf,
+    manufacturer: Option<String>,
+    model: Option<String>,
+    capacity: Option<u8>,
+    status: Option<String>,
+    start_threshold: Option<u8>,
+    end_threshold: Option<u8>,
+    /// The start and end thresholds when they were read.
+    read_thresholds: (Option<u8>, Option<u8>),
+}
+
+impl Battery {
+    /// The name of the power supply (`BAT0`...).
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_name(&self) -> String {
+        match &self.model {
+            Some(model) => format!("{} ({})", self.id, model),
+            None => self.id.clone(),
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(manufacturer) = &self.manufacturer {
+            parts.push(manufacturer.clone());
+        }
+        if let Some(capacity) = self.capacity {
+            parts.push(format!("{}%", capacity));
+        }
+        if let Some(status) = &self.status {
+            parts.push(status.clone());
+        }
+
+        parts.join(", ")
+    }
+
+    /// The charge level under which charging starts again, in percent.
+    pub fn start_threshold(&self) -> Option<u8> {
+        self.start_threshold
+    }
+
+    /// The charge level at which charging stops, in percent.
+    pub fn end_threshold(&self) -> Option<u8> {
+        self.end_threshold
+    }
+
+    /// Changes the thresholds this battery supports, the other ones are ignored.
+    pub fn set_thresholds(&mut self, start: u8, end: u8) -> Result<()> {
+        if end > 100 || start > 100 {
+            bail!("thresholds must be between 0 and 100%");
+        }
+        if self.start_threshold.is_some() && self.end_threshold.is_some() && start >= end {
+            bail!("the start threshold must be below the end threshold");
+        }
+
+        if self.start_threshold.is_some() {
+            self.start_threshold = Some(start);
+        }
+        if self.end_threshold.is_some() {
+            self.end_threshold = Some(end);
+        }
+
+        Ok(())
+    }
+
+    /// The settings of this battery, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let mut settings = Vec::new();
+        if let Some(start) = self.start_threshold {
+            settings.push((START_THRESHOLD.to_owned(), start.to_string()));
+        }
+        if let Some(end) = self.end_threshold {
+            settings.push((END_THRESHOLD.to_owned(), end.to_string()));
+        }
+
+        settings
+    }
+
+    /// Checks the start threshold is below the end one, when both are supported.
+    pub fn check_thresholds(&self) -> Result<()> {
+        match (self.start_threshold, self.end_threshold) {
+            (Some(start), Some(end)) if start >= end => {
+                bail!("the start threshold must be below the end threshold")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    ///
+    /// The thresholds aren't checked against each other, as the profile may
+    /// set them in any order: [`check_thresholds`](Self::check_thresholds)
+    /// must be called once the whole section is applied.
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        let value: u8 = value.parse()?;
+        if value > 100 {
+            bail!("invalid value '{}' for '{}'", value, key);
+        }
+        let threshold = match key {
+            START_THRESHOLD => &mut self.start_threshold,
+            END_THRESHOLD => &mut self.end_threshold,
+            _ => bail!("unknown battery setting '{}'", key),
+        };
+        if threshold.is_none() {
+            bail!("{} doesn't support '{}'", self.id, key);
+        }
+        *threshold = Some(value);
+
+        Ok(())
+    }
+
+    /// Writes the thresholds that were changed since they were read, in an
+    /// order that never has the kernel see a start threshold above the end one.
+    ///
+    /// Some firmware refuses writes to the start threshold, or most end
+    /// values, so the unchanged ones are left alone.
+    pub async fn save(&self) -> Result<()> {
+        let (read_start, read_end) = self.read_thresholds;
+
+        let mut writes = Vec::new();
+        if let (Some(start), true) = (self.start_threshold, self.start_threshold != read_start) {
+            writes.push((START_THRESHOLD, start));
+        }
+        if let (Some(end), true) = (self.end_threshold, self.end_threshold != read_end) {
+            match (self.start_threshold, read_end) {
+                (Some(start), Some(read_end)) if start >= read_end => {
+                    writes.insert(0, (END_THRESHOLD, end))
+                }
+                _ => writes.push((END_THRESHOLD, end)),
+            }
+        }
+
+        for (name, value) in writes {
+            trace!("saving '{}' with {}", self.path.join(name).display(), value);
+            write_string_privileged(&self.path.join(name), value.to_string()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What a battery reports at one point in time, read again on every refresh
+/// of the summary.
+#[derive(Clone, Debug)]
+pub struct BatteryReading {
+    pub capacity: Option<u8>,
+    pub status: Option<String>,
+    /// Remaining energy, in µWh.
+    pub energy_now: Option<u64>,
+    /// Energy when fully charged, in µWh.
+    pub energy_full: Option<u64>,
+    /// Instantaneous power going in or out of the battery, in µW.
+    pub power_now: Option<u64>,
+}
+
+impl Battery {
+    /// Reads the current state of the battery, which fails if it was removed.
+    pub fn read(&self) -> Result<BatteryReading> {
+        if !self.path.exists() {
+            bail!("{} is gone", self.id);
+        }
+        let read = |name: &str| {
+            fs::read_to_string(self.path.join(name))
+                .ok()
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+        };
+
+        Ok(BatteryReading {
+            capacity: read("capacity").and_then(|c| c.parse().ok()),
+            status: read("status"),
+            energy_now: read("energy_now").and_then(|e| e.parse().ok()),
+            energy_full: read("energy_full").and_then(|e| e.parse().ok()),
+            // some drivers only report a draw while discharging
+            power_now: read("power_now")
+                .and_then(|p| p.parse().ok())
+                .filter(|p| *p > 0),
+        })
+    }
+}
+
+/// Whether the computer is plugged in, `None` if it has no external power
+/// supply to tell.
+pub fn ac_online() -> Option<bool> {
+    let mut online = None;
+
+    for entry in fs::read_dir("/sys/class/power_supply/").ok()?.flatten() {
+        let path = entry.path();
+        let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+        // USB-C chargers show up as "USB"
+        if kind.trim() == "Mains" || kind.trim() == "USB" {
+            let state = fs::read_to_string(path.join("online")).unwrap_or_default();
+            match state.trim() {
+                "1" => return Some(true),
+                "0" => online = Some(false),
+                _ => (),
+            }
+        }
+    }
+
+    online
+}
+
+pub fn list_batteries() -> Result<Vec<Battery>> {
+    debug!("listing batteries");
+
+    let mut batteries = Vec::new();
+
+    let dir = PathBuf::from("/sys/class/power_supply/");
+    for entry in fs::read_dir(&dir)? {
+        match_warn!(entry, "ignoring error while enumerating power supplies: {}", entry => {
+            let path = entry.path();
+            let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+            if kind.trim() == "Battery" {
+                let battery = make_battery(path);
+                match_warn!(battery, "ignoring error reading battery: {}", battery => {
+                    batteries.push(battery);
+                });
+            }
+        });
+    }
+
+    batteries.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(batteries)
+}
+
+fn make_battery(path: PathBuf) -> Result<Battery> {
+    let id = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get battery name"),
+    };
+    let read = |name: &str| {
+        fs::read_to_string(path.join(name))
+            .ok()
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+    };
+
+    let start_threshold = read(START_THRESHOLD).and_then(|t| t.parse().ok());
+    let end_threshold = read(END_THRESHOLD).and_then(|t| t.parse().ok());
+
+    Ok(Battery {
+        id,
+        manufacturer: read("manufacturer"),
+        model: read("model_name"),
+        capacity: read("capacity").and_then(|c| c.parse().ok()),
+        status: read("status"),
+        start_threshold,
+        end_threshold,
+        read_thresholds: (start_threshold, end_threshold),
+        path: path.clone(),
+    })
+}
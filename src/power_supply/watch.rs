@@ -0,0 +1,55 @@
+use super::ac_online;
+use gio::prelude::*;
+use log::*;
+
+/// How often the power source is checked, in seconds.
+const POLL_INTERVAL: u32 = 5;
+
+/// Calls `callback` every time the computer is plugged in or unplugged, with
+/// whether it now runs on AC power.
+///
+/// UPower is asked when it runs, as it knows about power sources sysfs doesn't
+/// describe well (docks, USB-C chargers...). The power supplies in sysfs are
+/// polled otherwise.
+pub fn watch_power_source<F: Fn(bool) + 'static>(callback: F) {
+    let upower = match gio::DBusProxy::new_for_bus_sync(
+        gio::BusType::System,
+        gio::DBusProxyFlags::DO_NOT_AUTO_START,
+        None,
+        "org.freedesktop.UPower",
+        "/org/freedesktop/UPower",
+        "org.freedesktop.UPower",
+        gio::NONE_CANCELLABLE,
+    ) {
+        Ok(proxy) => Some(proxy),
+        Err(e) => {
+            debug!("UPower not available, polling sysfs: {}", e);
+            None
+        }
+    };
+
+    // the cached property is kept up to date by the proxy
+    let read = move || {
+        upower
+            .as_ref()
+            .filter(|p| p.get_name_owner().is_some())
+            .and_then(|p| p.get_cached_property("OnBattery"))
+            .and_then(|v| v.get::<bool>())
+            .map(|on_battery| !on_battery)
+            .or_else(ac_online)
+    };
+
+    let mut last = read();
+    debug!("watching power source, on AC: {:?}", last);
+    glib::timeout_add_seconds_local(POLL_INTERVAL, move || {
+        // an unknown state is never a change
+        if let Some(on_ac) = read() {
+            if last.is_some() && last != Some(on_ac) {
+                callback(on_ac);
+            }
+            last = Some(on_ac);
+        }
+
+        glib::Continue(true)
+    });
+}
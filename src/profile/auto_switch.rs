@@ -0,0 +1,105 @@
+use super::config_dir;
+use anyhow::*;
+use log::*;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+/// Which profiles to apply when the computer is plugged in or unplugged.
+///
+/// It is stored next to the profiles as a small text file:
+///
+/// ```text
+/// enabled = true
+/// ac = performance
+/// battery = powersave
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AutoSwitch {
+    pub enabled: bool,
+    pub on_ac: Option<String>,
+    pub on_battery: Option<String>,
+}
+
+impl AutoSwitch {
+    /// The profile to apply for a power source, if any.
+    pub fn profile_for(&self, on_ac: bool) -> Option<&str> {
+        if !self.enabled {
+            return None;
+        }
+
+        if on_ac {
+            self.on_ac.as_deref()
+        } else {
+            self.on_battery.as_deref()
+        }
+    }
+
+    fn parse(content: &str) -> Result<AutoSwitch> {
+        let mut auto_switch = AutoSwitch::default();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => bail!("line {}: expected 'key = value'", number + 1),
+            };
+            let profile = Some(value.to_owned()).filter(|v| !v.is_empty());
+            match key {
+                "enabled" => auto_switch.enabled = value.parse()?,
+                "ac" => auto_switch.on_ac = profile,
+                "battery" => auto_switch.on_battery = profile,
+                _ => warn!("ignoring unknown setting '{}'", key),
+            }
+        }
+
+        Ok(auto_switch)
+    }
+}
+
+impl Display for AutoSwitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        writeln!(f, "# GPower Tweaks automatic profile switching")?;
+        writeln!(f, "enabled = {}", self.enabled)?;
+        if let Some(profile) = &self.on_ac {
+            writeln!(f, "ac = {}", profile)?;
+        }
+        if let Some(profile) = &self.on_battery {
+            writeln!(f, "battery = {}", profile)?;
+        }
+        Ok(())
+    }
+}
+
+fn auto_switch_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("auto-switch.conf"))
+}
+
+/// Reads the automatic switching settings, which are disabled if they were
+/// never saved.
+pub fn load_auto_switch() -> Result<AutoSwitch> {
+    let path = auto_switch_path()?;
+    if !path.exists() {
+        return Ok(AutoSwitch::default());
+    }
+    debug!("loading automatic switching from {}", path.display());
+
+    let content = fs::read_to_string(&path)?;
+
+    AutoSwitch::parse(&content).with_context(|| format!("invalid file '{}'", path.display()))
+}
+
+pub fn save_auto_switch(auto_switch: &AutoSwitch) -> Result<()> {
+    let path = auto_switch_path()?;
+    debug!("saving automatic switching to {}", path.display());
+
+    fs::create_dir_all(config_dir()?)?;
+    fs::write(&path, auto_switch.to_string())?;
+
+    Ok(())
+}
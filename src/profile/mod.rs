@@ -0,0 +1,185 @@
+mod auto_switch;
+
+pub use auto_switch::{load_auto_switch, save_auto_switch, AutoSwitch};
+
+use anyhow::*;
+use log::*;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+/// A named set of settings that can be saved, and applied again later.
+///
+/// Profiles are stored as text files, one section per device (or group of
+/// devices) with the sysfs attributes to set:
+///
+/// ```text
+/// [usb vendor=046d,product=c52b]
+/// control = auto
+/// autosuspend_delay_ms = 2000
+/// ```
+///
+/// The section header starts with the kind of the section (`usb`, `pci`…),
+/// followed by a target whose meaning depends on the kind, usually a device
+/// matcher.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    name: String,
+    sections: Vec<Section>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub kind: String,
+    pub target: String,
+    pub settings: Vec<(String, String)>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Profile {
+        Profile {
+            name: name.to_owned(),
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn add_section(&mut self, kind: &str, target: &str, settings: Vec<(String, String)>) {
+        if settings.is_empty() {
+            return;
+        }
+
+        self.sections.push(Section {
+            kind: kind.to_owned(),
+            target: target.to_owned(),
+            settings,
+        });
+    }
+
+    pub fn parse(name: &str, content: &str) -> Result<Profile> {
+        let mut profile = Profile::new(name);
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let header = line[1..line.len() - 1].trim();
+                let mut parts = header.splitn(2, ' ');
+                profile.sections.push(Section {
+                    kind: parts.next().unwrap_or_default().to_owned(),
+                    target: parts.next().unwrap_or_default().trim().to_owned(),
+                    settings: Vec::new(),
+                });
+            } else {
+                let mut parts = line.splitn(2, '=');
+                match (parts.next(), parts.next(), profile.sections.last_mut()) {
+                    (Some(key), Some(value), Some(section)) => section
+                        .settings
+                        .push((key.trim().to_owned(), value.trim().to_owned())),
+                    (_, _, None) => bail!("line {}: setting outside of a section", number + 1),
+                    _ => bail!("line {}: expected 'key = value'", number + 1),
+                }
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        writeln!(f, "# GPower Tweaks profile '{}'", self.name)?;
+        for section in &self.sections {
+            writeln!(f)?;
+            if section.target.is_empty() {
+                writeln!(f, "[{}]", section.kind)?;
+            } else {
+                writeln!(f, "[{} {}]", section.kind, section.target)?;
+            }
+            for (key, value) in &section.settings {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The directory holding the configuration of the application.
+pub fn config_dir() -> Result<PathBuf> {
+    let config_dir =
+        glib::get_user_config_dir().ok_or_else(|| anyhow!("no user configuration directory"))?;
+
+    Ok(config_dir.join("gpower-tweaks"))
+}
+
+pub fn profiles_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("profiles"))
+}
+
+fn profile_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        bail!("invalid profile name '{}'", name);
+    }
+
+    Ok(profiles_dir()?.join(format!("{}.profile", name)))
+}
+
+pub fn list_profiles() -> Result<Vec<String>> {
+    debug!("listing profiles");
+
+    let mut names = Vec::new();
+    let dir = profiles_dir()?;
+    if !dir.exists() {
+        return Ok(names);
+    }
+
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().map(|e| e == "profile").unwrap_or(false) {
+            if let Some(name) = path.file_stem() {
+                names.push(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+pub fn load_profile(name: &str) -> Result<Profile> {
+    let path = profile_path(name)?;
+    debug!("loading profile from {}", path.display());
+
+    let content = fs::read_to_string(&path)?;
+
+    Profile::parse(name, &content).with_context(|| format!("invalid profile '{}'", name))
+}
+
+pub fn save_profile(profile: &Profile) -> Result<()> {
+    let path = profile_path(profile.get_name())?;
+    debug!("saving profile to {}", path.display());
+
+    fs::create_dir_all(profiles_dir()?)?;
+    fs::write(&path, profile.to_string())?;
+
+    Ok(())
+}
+
+pub fn delete_profile(name: &str) -> Result<()> {
+    let path = profile_path(name)?;
+    debug!("deleting profile {}", path.display());
+
+    fs::remove_file(&path)?;
+
+    Ok(())
+}
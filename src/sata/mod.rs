@@ -0,0 +1,189 @@
+use crate::fs::write_string_privileged;
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The policies the kernel accepts, from the most to the least power hungry.
+pub const POLICIES: [&str; 5] = [
+    "max_performance",
+    "medium_power",
+    "med_power_with_dipm",
+    "min_power",
+    "keep_firmware_settings",
+];
+
+/// A SCSI host whose link power management can be controlled (an AHCI port).
+#[derive(Clone, Debug)]
+pub struct SataHost {
+    id: String,
+    host_path: PathBuf,
+    policy: String,
+    /// The policy when it was read.
+    read_policy: String,
+    pci_slot: Option<String>,
+    port: Option<u32>,
+}
+
+impl SataHost {
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// The slot of the PCI device (the SATA controller) this host belongs to.
+    pub fn pci_slot(&self) -> Option<&str> {
+        self.pci_slot.as_deref()
+    }
+
+    /// Identifies the host in a [`Profile`](crate::profile::Profile).
+    ///
+    /// Host numbers depend on the order the controllers were probed in, so the
+    /// controller and the port on it are used when they are known.
+    pub fn profile_target(&self) -> String {
+        match (&self.pci_slot, self.port) {
+            (Some(slot), Some(port)) => format!("slot={},port={}", slot, port),
+            _ => self.id.clone(),
+        }
+    }
+
+    pub fn policy(&self) -> &str {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: &str) -> Result<()> {
+        if !POLICIES.contains(&policy) {
+            bail!("unknown link power management policy '{}'", policy);
+        }
+        self.policy = policy.to_owned();
+
+        Ok(())
+    }
+
+    pub fn get_policy_description(policy: &str) -> &'static str {
+        match policy {
+            "max_performance" => "Maximum performance",
+            "medium_power" => "Medium power",
+            "med_power_with_dipm" => "Medium power with Device Initiated PM",
+            "min_power" => "Minimum power",
+            "keep_firmware_settings" => "Firmware settings",
+            _ => "Unknown",
+        }
+    }
+
+    /// The settings of this host, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        vec![(
+            "link_power_management_policy".to_owned(),
+            self.policy.clone(),
+        )]
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "link_power_management_policy" => self.set_policy(value),
+            _ => bail!("unknown sata setting '{}'", key),
+        }
+    }
+
+    /// Writes the policy if it was changed since it was read, returning
+    /// whether it was written.
+    ///
+    /// Hosts with a quirk disabling link power management refuse any write,
+    /// even of an unchanged policy.
+    pub async fn save(&self) -> Result<bool> {
+        if self.policy == self.read_policy {
+            return Ok(false);
+        }
+
+        let policy_path = self.host_path.join("link_power_management_policy");
+
+        trace!(
+            "saving '{}' with {}",
+            policy_path.to_string_lossy(),
+            self.policy
+        );
+
+        write_string_privileged(&policy_path, self.policy.clone()).await?;
+
+        Ok(true)
+    }
+}
+
+pub fn list_hosts() -> Result<Vec<SataHost>> {
+    debug!("listing sata hosts");
+
+    let mut hosts = Vec::new();
+
+    let dir = PathBuf::from("/sys/class/scsi_host/");
+    for entry in fs::read_dir(&dir)? {
+        match_warn!(entry, "ignoring error while enumerating hosts: {}", entry => {
+            let path = entry.path();
+            // only AHCI hosts have a link power management policy
+            if path.join("link_power_management_policy").exists() {
+                let host = make_host(path);
+                match_warn!(host, "ignoring error reading host: {}", host => {
+                    hosts.push(host);
+                });
+            }
+        });
+    }
+
+    hosts.sort_by_key(|h| host_number(&h.id));
+
+    Ok(hosts)
+}
+
+fn host_number(id: &str) -> u32 {
+    id.trim_start_matches("host").parse().unwrap_or(u32::MAX)
+}
+
+fn make_host(path: PathBuf) -> Result<SataHost> {
+    let id = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get host name"),
+    };
+
+    let policy = fs::read_to_string(path.join("link_power_management_policy"))?
+        .trim()
+        .to_owned();
+    let real_path = path.canonicalize().ok();
+    let pci_slot = real_path.as_deref().and_then(find_pci_slot);
+    let port = real_path.as_deref().and_then(find_port);
+
+    Ok(SataHost {
+        id,
+        host_path: path,
+        read_policy: policy.clone(),
+        policy,
+        pci_slot,
+        port,
+    })
+}
+
+/// Reads the port number of the `ataN` directory above a host, which is
+/// numbered in probe order like the host.
+fn find_port(path: &Path) -> Option<u32> {
+    let ata = path.ancestors().find(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.starts_with("ata") && n[3..].parse::<u32>().is_ok())
+            .unwrap_or(false)
+    })?;
+    let port_no = ata.join("ata_port").join(ata.file_name()?).join("port_no");
+
+    fs::read_to_string(port_no).ok()?.trim().parse().ok()
+}
+
+/// Finds the closest PCI device above a sysfs path, looking like
+/// `/sys/devices/pci0000:00/0000:00:17.0/ata1/host0/scsi_host/host0`.
+fn find_pci_slot(path: &Path) -> Option<String> {
+    path.ancestors()
+        .find(|p| {
+            fs::read_link(p.join("subsystem"))
+                .map(|s| s.ends_with("pci"))
+                .unwrap_or(false)
+        })
+        .and_then(|p| p.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+}
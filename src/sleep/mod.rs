@@ -0,0 +1,85 @@
+use crate::fs::{read_choices, write_string_privileged};
+use anyhow::*;
+use log::*;
+use std::path::Path;
+
+const MEM_SLEEP_PATH: &str = "/sys/power/mem_sleep";
+/// Where [`MemSleep::tmpfiles_snippet`] is meant to be written.
+pub const TMPFILES_PATH: &str = "/etc/tmpfiles.d/gpower-tweaks-mem-sleep.conf";
+
+/// The suspend variant used when suspending to memory.
+#[derive(Clone, Debug)]
+pub struct MemSleep {
+    current: String,
+    available: Vec<String>,
+    /// The mode when it was read.
+    read: String,
+}
+
+impl MemSleep {
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn available(&self) -> &[String] {
+        &self.available
+    }
+
+    pub fn set(&mut self, mode: &str) -> Result<()> {
+        if !self.available.iter().any(|m| m == mode) {
+            bail!("unknown suspend mode '{}'", mode);
+        }
+
+        self.current = mode.to_owned();
+
+        Ok(())
+    }
+
+    pub fn get_description(mode: &str) -> &'static str {
+        match mode {
+            "s2idle" => "Suspend-to-idle",
+            "shallow" => "Standby",
+            "deep" => "Suspend-to-RAM",
+            _ => "Unknown",
+        }
+    }
+
+    /// The kernel command-line parameter selecting the current mode at boot.
+    pub fn kernel_parameter(&self) -> String {
+        format!("mem_sleep_default={}", self.current)
+    }
+
+    /// A `tmpfiles.d` configuration setting the current mode at boot.
+    pub fn tmpfiles_snippet(&self) -> String {
+        format!(
+            "# Written by gpower-tweaks\n# Suspend mode, the kernel command-line parameter {} does the same\nw {} - - - - {}\n",
+            self.kernel_parameter(),
+            MEM_SLEEP_PATH,
+            self.current
+        )
+    }
+
+    /// Writes the mode if it was changed since it was read.
+    pub async fn save(&self) -> Result<()> {
+        if self.current == self.read {
+            return Ok(());
+        }
+
+        trace!("saving '{}' with {}", MEM_SLEEP_PATH, self.current);
+
+        write_string_privileged(Path::new(MEM_SLEEP_PATH), self.current.clone()).await
+    }
+}
+
+/// Reads the suspend modes, if the kernel can suspend to memory.
+pub fn read_mem_sleep() -> Result<MemSleep> {
+    // like "s2idle [deep]"
+    let (current, available) = read_choices(Path::new(MEM_SLEEP_PATH))?;
+    let current = current.ok_or_else(|| anyhow!("no current suspend mode"))?;
+
+    Ok(MemSleep {
+        read: current.clone(),
+        current,
+        available,
+    })
+}
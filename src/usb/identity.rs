@@ -0,0 +1,256 @@
+use anyhow::*;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Identity of a USB device that stays the same across reboots and replugs.
+///
+/// The port (`busnum-devpath`) pins the device to where it is plugged in, while
+/// the vendor/product pair and the serial number (when the device reports one)
+/// describe the device itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UsbIdentity {
+    pub busnum: u16,
+    pub devpath: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial: Option<String>,
+}
+
+impl UsbIdentity {
+    pub fn port(&self) -> String {
+        format!("{}-{}", self.busnum, self.devpath)
+    }
+}
+
+impl Display for UsbIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} {:04x}:{:04x}",
+            self.port(),
+            self.vendor_id,
+            self.product_id
+        )?;
+        if let Some(serial) = &self.serial {
+            write!(f, " {}", serial)?;
+        }
+        Ok(())
+    }
+}
+
+/// A pattern over [`UsbIdentity`]: every field that is set must be equal.
+///
+/// It is written as a comma-separated list of `key=value` pairs, for example
+/// `vendor=046d,product=c52b,serial=1234` or `port=1-2.3`. Serial numbers are
+/// free text: the characters that would break that format are written as `%XX`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UsbMatcher {
+    pub port: Option<String>,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub serial: Option<String>,
+}
+
+impl UsbMatcher {
+    pub fn matches(&self, identity: &UsbIdentity) -> bool {
+        if let Some(port) = &self.port {
+            if *port != identity.port() {
+                return false;
+            }
+        }
+        if let Some(vendor_id) = self.vendor_id {
+            if vendor_id != identity.vendor_id {
+                return false;
+            }
+        }
+        if let Some(product_id) = self.product_id {
+            if product_id != identity.product_id {
+                return false;
+            }
+        }
+        if let Some(serial) = &self.serial {
+            if Some(serial) != identity.serial.as_ref() {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl From<&UsbIdentity> for UsbMatcher {
+    /// Builds a matcher for exactly this device.
+    ///
+    /// A serial number is enough to follow the device from port to port;
+    /// without one, the port is needed to tell identical devices apart.
+    fn from(identity: &UsbIdentity) -> Self {
+        UsbMatcher {
+            port: if identity.serial.is_some() {
+                None
+            } else {
+                Some(identity.port())
+            },
+            vendor_id: Some(identity.vendor_id),
+            product_id: Some(identity.product_id),
+            serial: identity.serial.clone(),
+        }
+    }
+}
+
+impl Display for UsbMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let mut fields = Vec::new();
+        if let Some(vendor_id) = self.vendor_id {
+            fields.push(format!("vendor={:04x}", vendor_id));
+        }
+        if let Some(product_id) = self.product_id {
+            fields.push(format!("product={:04x}", product_id));
+        }
+        if let Some(serial) = &self.serial {
+            fields.push(format!("serial={}", escape(serial)));
+        }
+        if let Some(port) = &self.port {
+            fields.push(format!("port={}", port));
+        }
+
+        f.write_str(&fields.join(","))
+    }
+}
+
+impl FromStr for UsbMatcher {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut matcher = UsbMatcher::default();
+
+        for field in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let mut parts = field.splitn(2, '=');
+            let key = parts.next().unwrap_or_default().trim();
+            let value = parts
+                .next()
+                .ok_or_else(|| anyhow!("missing value for '{}' in usb matcher", key))?
+                .trim();
+
+            match key {
+                "port" => matcher.port = Some(value.to_owned()),
+                "vendor" => matcher.vendor_id = Some(u16::from_str_radix(value, 16)?),
+                "product" => matcher.product_id = Some(u16::from_str_radix(value, 16)?),
+                "serial" => matcher.serial = Some(unescape(value)?),
+                _ => bail!("unknown usb matcher key '{}'", key),
+            }
+        }
+
+        Ok(matcher)
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | '%' => escaped.push_str(&format!("%{:02X}", c as u8)),
+            c if c.is_ascii_whitespace() || c.is_ascii_control() => {
+                escaped.push_str(&format!("%{:02X}", c as u8))
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(value: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail
+                .get(..2)
+                .ok_or_else(|| anyhow!("truncated escape in '{}'", value))?;
+            // from_str_radix alone would also take a sign, like "+1"
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                bail!("invalid escape in '{}'", value);
+            }
+            let hex = std::str::from_utf8(hex)?;
+            bytes.push(u8::from_str_radix(hex, 16)?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher_with_serial(serial: &str) -> UsbMatcher {
+        UsbMatcher {
+            vendor_id: Some(0x046d),
+            product_id: Some(0xc52b),
+            serial: Some(serial.to_owned()),
+            ..UsbMatcher::default()
+        }
+    }
+
+    #[test]
+    fn parse_matcher() {
+        let matcher: UsbMatcher = " vendor=046d, product=C52B ,port=1-2.3,".parse().unwrap();
+
+        assert_eq!(
+            matcher,
+            UsbMatcher {
+                port: Some("1-2.3".to_owned()),
+                vendor_id: Some(0x046d),
+                product_id: Some(0xc52b),
+                serial: None,
+            }
+        );
+        assert_eq!(matcher.to_string(), "vendor=046d,product=c52b,port=1-2.3");
+    }
+
+    #[test]
+    fn parse_invalid_matcher() {
+        for s in &[
+            "vendor",
+            "vendor=xyz",
+            "color=red",
+            "serial=%4",
+            "serial=%+1",
+            "serial=%-1",
+        ] {
+            assert!(s.parse::<UsbMatcher>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn serial_round_trip() {
+        let serials = [
+            "1234",
+            "a,b",
+            "key=value",
+            "100%",
+            " spaced out ",
+            "tab\there",
+            "séri€",
+            "%41",
+        ];
+        for serial in &serials {
+            let matcher = matcher_with_serial(serial);
+            let written = matcher.to_string();
+
+            assert_eq!(written.matches(',').count(), 2, "{}", written);
+            assert_eq!(written.parse::<UsbMatcher>().unwrap(), matcher);
+        }
+    }
+
+    #[test]
+    fn serial_escapes() {
+        assert_eq!(escape("a,b=c%d e"), "a%2Cb%3Dc%25d%20e");
+        assert_eq!(unescape("a%2cb%3Dc").unwrap(), "a,b=c");
+        assert_eq!(unescape("séri€").unwrap(), "séri€");
+    }
+}
@@ -1,16 +1,24 @@
+mod identity;
+
+pub use identity::{UsbIdentity, UsbMatcher};
+
 use crate::db::{parse_db, Db};
-use crate::fs::write_string_privileged;
+use crate::fs::{block_devices_under, mounted_filesystems, write_string_privileged};
 use anyhow::*;
 use log::*;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fs;
-use std::os::linux::fs::*;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct UsbDevice {
-    id: u32,
-    char_device_path: PathBuf,
+    id: String,
+    identity: UsbIdentity,
+    device_path: PathBuf,
+    char_device_path: Option<PathBuf>,
     vendor_id: Option<u16>,
     product_id: Option<u16>,
     db_vendor_name: Option<String>,
@@ -20,14 +28,35 @@ pub struct UsbDevice {
     autosuspend: bool,
     allow_wakeup: Option<bool>,
     delay: u64,
+    /// The autosuspend, delay and wakeup settings when they were read, the
+    /// delay is `None` when it was -1 (never suspend).
+    read_power: (bool, Option<u64>, Option<bool>),
     kind: UsbKind,
+    interfaces: Vec<UsbInterface>,
+    devnum: Option<u16>,
+    speed: Option<String>,
+    max_power: Option<String>,
+    removable: Option<String>,
+    version: Option<String>,
+    authorized: Option<bool>,
+    read_authorized: Option<bool>,
+    authorized_default: Option<UsbAuthorizedDefault>,
+    read_authorized_default: Option<UsbAuthorizedDefault>,
+    usb2_hardware_lpm: Option<bool>,
+    read_usb2_hardware_lpm: Option<bool>,
+    usb3_hardware_lpm_u1: Option<bool>,
+    usb3_hardware_lpm_u2: Option<bool>,
+    persist: Option<bool>,
+    read_persist: Option<bool>,
 }
 
 impl UsbDevice {
-    fn from(char_device_path: &Path, id: u32) -> UsbDevice {
+    fn from(device_path: PathBuf, id: String) -> UsbDevice {
         UsbDevice {
             id,
-            char_device_path: char_device_path.into(),
+            identity: UsbIdentity::default(),
+            device_path,
+            char_device_path: None,
             vendor_id: None,
             product_id: None,
             db_vendor_name: None,
@@ -37,12 +66,37 @@ impl UsbDevice {
             autosuspend: false,
             allow_wakeup: None,
             delay: 0,
+            read_power: (false, None, None),
             kind: UsbKind::default(),
+            interfaces: Vec::new(),
+            devnum: None,
+            speed: None,
+            max_power: None,
+            removable: None,
+            version: None,
+            authorized: None,
+            read_authorized: None,
+            authorized_default: None,
+            read_authorized_default: None,
+            usb2_hardware_lpm: None,
+            read_usb2_hardware_lpm: None,
+            usb3_hardware_lpm_u1: None,
+            usb3_hardware_lpm_u2: None,
+            persist: None,
+            read_persist: None,
         }
     }
 
-    pub fn get_id(&self) -> u32 {
-        self.id
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn identity(&self) -> &UsbIdentity {
+        &self.identity
+    }
+
+    pub fn matches(&self, matcher: &UsbMatcher) -> bool {
+        matcher.matches(&self.identity)
     }
 
     pub fn get_name(&self) -> String {
@@ -84,12 +138,17 @@ impl UsbDevice {
         }
 
         if desc.is_empty() {
-            desc = format!("{}", self.char_device_path.display());
+            desc = format!("{}", self.device_path.display());
         }
 
         desc
     }
 
+    /// The device node under `/dev/bus/usb`, if the kernel reported one.
+    pub fn char_device_path(&self) -> Option<&Path> {
+        self.char_device_path.as_deref()
+    }
+
     pub fn can_autosuspend(&self) -> bool {
         self.autosuspend
     }
@@ -181,6 +240,59 @@ impl UsbDevice {
         &self.kind
     }
 
+    pub fn interfaces(&self) -> &[UsbInterface] {
+        &self.interfaces
+    }
+
+    pub fn devnum(&self) -> Option<u16> {
+        self.devnum
+    }
+
+    /// The negotiated speed, as reported by the kernel (e.g. `480 Mb/s`).
+    pub fn speed(&self) -> Option<String> {
+        self.speed.as_ref().map(|s| format!("{} Mb/s", s))
+    }
+
+    /// The maximum power the device draws from the bus (e.g. `500mA`).
+    pub fn max_power(&self) -> Option<&str> {
+        self.max_power.as_deref()
+    }
+
+    /// Whether the port the device is on is `removable` or `fixed`.
+    pub fn removable(&self) -> Option<&str> {
+        self.removable.as_deref()
+    }
+
+    /// The USB specification version the device complies with (e.g. `2.00`).
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Explains why enabling autosuspend would have no effect on this device.
+    ///
+    /// The kernel only suspends a device when the drivers of all its interfaces
+    /// support it, so a single unsupported driver is enough to keep it awake.
+    pub fn autosuspend_unsupported_reason(&self) -> Option<String> {
+        let drivers = self
+            .interfaces
+            .iter()
+            .filter(|i| i.supports_autosuspend == Some(false))
+            .map(|i| match &i.driver {
+                Some(driver) => format!("{} (interface {})", driver, i.number),
+                None => format!("interface {}", i.number),
+            })
+            .collect::<Vec<_>>();
+
+        if drivers.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Autosuspend has no effect: the driver for {} does not support it.",
+                drivers.join(", ")
+            ))
+        }
+    }
+
     pub fn set_autosuspend(&mut self, autosuspend: bool) {
         self.autosuspend = autosuspend;
     }
@@ -190,8 +302,8 @@ impl UsbDevice {
     }
 
     pub async fn save(&self) -> Result<()> {
-        let control_path = self.char_device_path.join("power/control");
-        let autosuspend_delay_path = self.char_device_path.join("power/autosuspend_delay_ms");
+        let control_path = self.device_path.join("power/control");
+        let autosuspend_delay_path = self.device_path.join("power/autosuspend_delay_ms");
 
         let control_text = if self.autosuspend {
             "auto".to_string()
@@ -202,17 +314,248 @@ impl UsbDevice {
 
         trace!(
             "saving '{}' with ({}, {})",
-            self.char_device_path.to_string_lossy(),
+            self.device_path.to_string_lossy(),
             control_text,
             autosuspend_delay_text
         );
 
-        write_string_privileged(&control_path, control_text).await?;
-        write_string_privileged(&autosuspend_delay_path, autosuspend_delay_text).await?;
+        // only what changed is written, the kernel rejects some unchanged
+        // values and the ACPI wakeup table changes the wakeup setting
+        let (read_autosuspend, read_delay, read_allow_wakeup) = self.read_power;
+        if self.autosuspend != read_autosuspend {
+            write_string_privileged(&control_path, control_text).await?;
+        }
+        if Some(self.delay) != read_delay {
+            write_string_privileged(&autosuspend_delay_path, autosuspend_delay_text).await?;
+        }
+
+        if let (Some(wakeup), true) = (self.allow_wakeup, self.allow_wakeup != read_allow_wakeup) {
+            let wakeup_path = self.device_path.join("power/wakeup");
+            let wakeup_text = if wakeup { "enabled" } else { "disabled" };
+            write_string_privileged(&wakeup_path, wakeup_text.to_string()).await?;
+        }
+
+        if let (Some(persist), true) = (self.persist, self.persist != self.read_persist) {
+            let persist_path = self.device_path.join("power/persist");
+            let persist_text = if persist { "1" } else { "0" };
+            write_string_privileged(&persist_path, persist_text.to_string()).await?;
+        }
+
+        if let (Some(lpm), true) = (
+            self.usb2_hardware_lpm,
+            self.usb2_hardware_lpm != self.read_usb2_hardware_lpm,
+        ) {
+            let lpm_path = self.device_path.join("power/usb2_hardware_lpm");
+            let lpm_text = if lpm { "1" } else { "0" };
+            write_string_privileged(&lpm_path, lpm_text.to_string()).await?;
+        }
+
+        if let (Some(authorized_default), true) = (
+            self.authorized_default,
+            self.authorized_default != self.read_authorized_default,
+        ) {
+            let authorized_default_path = self.device_path.join("authorized_default");
+            write_string_privileged(&authorized_default_path, authorized_default.to_string())
+                .await?;
+        }
+
+        // last, since a deauthorized device loses its interfaces
+        if let (Some(authorized), true) = (self.authorized, self.authorized != self.read_authorized)
+        {
+            let authorized_path = self.device_path.join("authorized");
+            let authorized_text = if authorized { "1" } else { "0" };
+            write_string_privileged(&authorized_path, authorized_text.to_string()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the device is allowed to be used, if it can be (de)authorized at all.
+    pub fn authorized(&self) -> Option<bool> {
+        self.authorized
+    }
+
+    pub fn set_authorized(&mut self, authorized: bool) -> Result<()> {
+        if self.authorized.is_none() {
+            bail!("device doesn't support authorization");
+        }
+
+        self.authorized = Some(authorized);
+
+        Ok(())
+    }
+
+    /// Which newly connected devices are authorized by default (root hubs only).
+    pub fn authorized_default(&self) -> Option<UsbAuthorizedDefault> {
+        self.authorized_default
+    }
+
+    pub fn set_authorized_default(
+        &mut self,
+        authorized_default: UsbAuthorizedDefault,
+    ) -> Result<()> {
+        if self.authorized_default.is_none() {
+            bail!("device is not a root hub");
+        }
+
+        self.authorized_default = Some(authorized_default);
+
+        Ok(())
+    }
+
+    /// Whether USB 2 hardware Link Power Management is enabled, if the device supports it.
+    pub fn usb2_hardware_lpm(&self) -> Option<bool> {
+        self.usb2_hardware_lpm
+    }
+
+    pub fn set_usb2_hardware_lpm(&mut self, enabled: bool) -> Result<()> {
+        if self.usb2_hardware_lpm.is_none() {
+            bail!("device doesn't support USB 2 hardware LPM");
+        }
+
+        self.usb2_hardware_lpm = Some(enabled);
+
+        Ok(())
+    }
+
+    /// Whether the U1 link state is enabled, if the device supports USB 3 LPM.
+    ///
+    /// The kernel only reports this one, it decides on its own whether to
+    /// enable it.
+    pub fn usb3_hardware_lpm_u1(&self) -> Option<bool> {
+        self.usb3_hardware_lpm_u1
+    }
+
+    /// Whether the U2 link state is enabled, if the device supports USB 3 LPM.
+    ///
+    /// The kernel only reports this one, it decides on its own whether to
+    /// enable it.
+    pub fn usb3_hardware_lpm_u2(&self) -> Option<bool> {
+        self.usb3_hardware_lpm_u2
+    }
+
+    /// Whether the device keeps its identity when it loses power during suspend.
+    pub fn persist(&self) -> Option<bool> {
+        self.persist
+    }
+
+    pub fn set_persist(&mut self, persist: bool) -> Result<()> {
+        if self.persist.is_none() {
+            bail!("device doesn't support persist");
+        }
+
+        self.persist = Some(persist);
 
         Ok(())
     }
 
+    /// Mount points of the filesystems on block devices provided by this device.
+    pub fn mounted_filesystems(&self) -> Vec<String> {
+        let block_devices = block_devices_under(&self.device_path);
+        match mounted_filesystems(&block_devices) {
+            Ok(mount_points) => mount_points,
+            Err(e) => {
+                warn!("ignoring error reading mounts: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// The settings of this device, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let mut settings = Vec::new();
+        let flag = |b: bool| if b { "1" } else { "0" }.to_owned();
+
+        let control = if self.autosuspend { "auto" } else { "on" };
+        settings.push(("control".to_owned(), control.to_owned()));
+        settings.push(("autosuspend_delay_ms".to_owned(), self.delay.to_string()));
+        if let Some(wakeup) = self.allow_wakeup {
+            let wakeup = if wakeup { "enabled" } else { "disabled" };
+            settings.push(("wakeup".to_owned(), wakeup.to_owned()));
+        }
+        if let Some(lpm) = self.usb2_hardware_lpm {
+            settings.push(("usb2_hardware_lpm".to_owned(), flag(lpm)));
+        }
+        if let Some(persist) = self.persist {
+            settings.push(("persist".to_owned(), flag(persist)));
+        }
+        if let Some(authorized_default) = self.authorized_default {
+            settings.push((
+                "authorized_default".to_owned(),
+                authorized_default.to_string(),
+            ));
+        }
+
+        settings
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        let flag = |v: &str| match v {
+            "1" => Ok(true),
+            "0" => Ok(false),
+            v => Err(anyhow!("invalid value '{}' for '{}'", v, key)),
+        };
+
+        match key {
+            "control" => match value {
+                "auto" => self.autosuspend = true,
+                "on" => self.autosuspend = false,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            "autosuspend_delay_ms" => self.delay = value.parse()?,
+            "wakeup" => match value {
+                "enabled" => self.set_allow_wakeup(true)?,
+                "disabled" => self.set_allow_wakeup(false)?,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            "usb2_hardware_lpm" => self.set_usb2_hardware_lpm(flag(value)?)?,
+            "persist" => {
+                let persist = flag(value)?;
+                // profiles are applied without asking, so what the UI confirms is refused
+                if !persist && self.persist == Some(true) {
+                    let mount_points = self.mounted_filesystems();
+                    if !mount_points.is_empty() {
+                        bail!(
+                            "not turning off persist, filesystems are mounted from it ({})",
+                            mount_points.join(", ")
+                        );
+                    }
+                }
+                self.set_persist(persist)?
+            }
+            "authorized_default" => self.set_authorized_default(value.parse()?)?,
+            // deauthorizing needs a confirmation for input and storage devices
+            "authorized" => bail!("authorization isn't loaded from profiles"),
+            _ => bail!("unknown usb setting '{}'", key),
+        }
+
+        Ok(())
+    }
+
+    /// Whether this device (or one of its interfaces) is a HID or mass storage device.
+    ///
+    /// Deauthorizing those can leave the user without a keyboard, or pull a
+    /// mounted disk from under the system.
+    pub fn is_input_or_storage(&self) -> bool {
+        let is_input_or_storage = |kind: &UsbKind| kind.class == 0x03 || kind.class == 0x08;
+
+        is_input_or_storage(&self.kind)
+            || self.interfaces.iter().any(|i| is_input_or_storage(&i.kind))
+    }
+
+    /// Whether `other` is connected through this device, directly or through
+    /// other hubs. Deauthorizing a hub deauthorizes everything below it.
+    pub fn is_upstream_of(&self, other: &UsbDevice) -> bool {
+        let (hub, device) = (&self.identity, &other.identity);
+        if hub.busnum != device.busnum || device.devpath == "0" {
+            return false;
+        }
+
+        // root hubs have the devpath 0, and everything on their bus is below them
+        hub.devpath == "0" || device.devpath.starts_with(&format!("{}.", hub.devpath))
+    }
+
     /// Get a reference to the usb device's allow wakeup.
     pub fn allow_wakeup(&self) -> Option<bool> {
         self.allow_wakeup
@@ -221,7 +564,7 @@ impl UsbDevice {
     /// Set the usb device's allow wakeup.
     pub fn set_allow_wakeup(&mut self, allow_wakeup: bool) -> Result<()> {
         if self.allow_wakeup.is_none() {
-            anyhow!("device doesn't support remote wakeup");
+            bail!("device doesn't support remote wakeup");
         }
 
         self.allow_wakeup = Some(allow_wakeup);
@@ -230,6 +573,105 @@ impl UsbDevice {
     }
 }
 
+/// The `authorized_default` policy of a root hub.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsbAuthorizedDefault {
+    None,
+    All,
+    Internal,
+}
+
+impl UsbAuthorizedDefault {
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            UsbAuthorizedDefault::None => "No devices",
+            UsbAuthorizedDefault::All => "All devices",
+            UsbAuthorizedDefault::Internal => "Internal devices only",
+        }
+    }
+}
+
+impl Display for UsbAuthorizedDefault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            UsbAuthorizedDefault::None => f.write_str("0"),
+            UsbAuthorizedDefault::All => f.write_str("1"),
+            UsbAuthorizedDefault::Internal => f.write_str("2"),
+        }
+    }
+}
+
+impl FromStr for UsbAuthorizedDefault {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "0" => Ok(UsbAuthorizedDefault::None),
+            // older kernels report -1 for "authorize all"
+            "1" | "-1" => Ok(UsbAuthorizedDefault::All),
+            "2" => Ok(UsbAuthorizedDefault::Internal),
+            s => bail!("unknown authorized_default value '{}'", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UsbInterface {
+    id: String,
+    number: u8,
+    kind: UsbKind,
+    driver: Option<String>,
+    supports_autosuspend: Option<bool>,
+}
+
+impl UsbInterface {
+    fn from(id: String) -> UsbInterface {
+        UsbInterface {
+            id,
+            number: 0,
+            kind: UsbKind::default(),
+            driver: None,
+            supports_autosuspend: None,
+        }
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// The `bInterfaceNumber` of this interface.
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn kind(&self) -> &UsbKind {
+        &self.kind
+    }
+
+    /// The name of the kernel driver bound to this interface, if any.
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
+
+    /// Whether the bound driver (if any) lets the device autosuspend.
+    pub fn supports_autosuspend(&self) -> Option<bool> {
+        self.supports_autosuspend
+    }
+
+    pub fn get_description(&self) -> String {
+        let mut desc = format!("Interface {}: {}", self.number, self.kind);
+        if let Some(name) = &self.kind.interface_name {
+            desc.push_str(&format!(" ({})", name));
+        }
+        match &self.driver {
+            Some(driver) => desc.push_str(&format!(" [{}]", driver)),
+            None => desc.push_str(" [no driver]"),
+        }
+
+        desc
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UsbKind {
     pub class: u16,
@@ -253,6 +695,20 @@ impl UsbKind {
     }
 }
 
+impl UsbKind {
+    pub(crate) fn fill_names(&mut self, db: &Db) {
+        if let Some(c) = db.classes.get(&self.class) {
+            self.class_name = Some(c.name.clone());
+            if let Some(sub) = c.subclasses.get(&self.subclass) {
+                self.subclass_name = Some(sub.name.trim().to_string());
+                if let Some(int) = sub.subclasses.get(&self.interface) {
+                    self.interface_name = Some(int.name.trim().to_string());
+                }
+            }
+        }
+    }
+}
+
 impl Default for UsbKind {
     fn default() -> Self {
         UsbKind {
@@ -282,15 +738,6 @@ impl Display for UsbKind {
     }
 }
 
-macro_rules! match_warn {
-    ($content:expr, $format:expr, $bind:ident => $func:block) => {
-        match $content {
-            Err(e) => warn!($format, e),
-            Ok($bind) => $func,
-        }
-    };
-}
-
 pub fn list_devices() -> Result<Vec<UsbDevice>> {
     let db = parse_db("/usr/share/hwdata/usb.ids")
         .map_err(|e| {
@@ -302,22 +749,20 @@ pub fn list_devices() -> Result<Vec<UsbDevice>> {
 
     let mut devices = Vec::new();
 
-    for entry in std::fs::read_dir("/dev/bus/usb/")? {
+    let dir = PathBuf::from("/sys/bus/usb/devices/");
+    for entry in std::fs::read_dir(&dir)? {
         match_warn!(entry, "ignoring error while enumerating devices: {}", entry => {
-            match_warn!(entry.file_type(), "ignoring error getting type: {}", tpe => {
-                if tpe.is_dir() {
-                    match_warn!(entry.path().read_dir(), "ignoring error enumerating device: {}", dir => {
-                        for entry in dir {
-                            match_warn!(entry, "ignoring error enumerating device: {}", entry => {
-                                let dev = make_device(entry, db.as_ref());
-                                match_warn!(dev, "ignoring error reading device: {}", dev => {
-                                    trace!("made device: {:?}", dev);
-                                    devices.push(dev);
-                                });
-                            });
-                        }
-                    });
-                }
+            match_warn!(entry.path().canonicalize(), "error canonicalizing symlink: {}", path => {
+                match_warn!(read_uevent(&path), "ignoring error reading uevent: {}", uevent => {
+                    // interfaces are listed alongside devices, skip them
+                    if uevent.get("DEVTYPE").map(String::as_str) == Some("usb_device") {
+                        let dev = make_device(path, &uevent, db.as_ref());
+                        match_warn!(dev, "ignoring error reading device: {}", dev => {
+                            trace!("made device: {:?}", dev);
+                            devices.push(dev);
+                        });
+                    }
+                });
             });
         });
     }
@@ -325,126 +770,233 @@ pub fn list_devices() -> Result<Vec<UsbDevice>> {
     Ok(devices)
 }
 
-fn interface_info(device: &Path) -> Result<(u16, u16, u16)> {
+fn read_uevent(path: &Path) -> Result<HashMap<String, String>> {
+    let content = fs::read_to_string(path.join("uevent"))?;
+
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => Some((key.to_owned(), value.to_owned())),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+fn list_interfaces(device: &Path, usb_db: Option<&Db>) -> Result<Vec<UsbInterface>> {
     let prefix = format!(
         "{}:",
         device.file_name().unwrap_or_default().to_string_lossy()
     );
 
-    let mut class = 0;
-    let mut subclass = 0;
-    let mut protocol = 0;
+    let mut interfaces = Vec::new();
 
     for entry in std::fs::read_dir(&device)? {
-        match_warn!(entry, "ignoring error while enumerating devices: {}", entry => {
+        match_warn!(entry, "ignoring error while enumerating interfaces: {}", entry => {
             match_warn!(entry.file_type(), "ignoring error getting type: {}", tpe => {
                 if tpe.is_dir() && entry.file_name().to_string_lossy().starts_with(&prefix) {
-                    let interface_path = entry.path();
-                    let class_path = interface_path.join("bInterfaceClass");
-                    let subclass_path = interface_path.join("bInterfaceSubClass");
-                    let protocol_path = interface_path.join("bInterfaceProtocol");
-
-                    let mut new_class = 0;
-                    let mut new_subclass = 0;
-                    let mut new_protocol = 0;
-                    if let Ok(class_str) = fs::read_to_string(&class_path) {
-                        if let Ok(class_id) = u16::from_str_radix(&class_str.trim(), 16) {
-                            new_class = class_id;
-                        }
-                    }
+                    let interface = make_interface(entry.path(), usb_db);
+                    match_warn!(interface, "ignoring error reading interface: {}", interface => {
+                        interfaces.push(interface);
+                    });
+                }
+            });
+        });
+    }
 
-                    if let Ok(subclass_str) = fs::read_to_string(&subclass_path) {
-                        if let Ok(subclass_id) = u16::from_str_radix(&subclass_str.trim(), 16) {
-                            new_subclass = subclass_id;
-                        }
-                    }
+    interfaces.sort_by_key(|i| i.number);
 
-                    if let Ok(protocol_str) = fs::read_to_string(&protocol_path) {
-                        if let Ok(protocol_id) = u16::from_str_radix(&protocol_str.trim(), 16) {
-                            new_protocol = protocol_id;
-                        }
-                    }
+    Ok(interfaces)
+}
 
-                    // heuristic: when the current interface is a Mouse, don't override with a Keyboard
-                    if class == 0x03 && protocol == 0x02 && new_class == 0x03 && new_protocol == 0x01 {
-                        continue
-                    }
+fn make_interface(path: PathBuf, usb_db: Option<&Db>) -> Result<UsbInterface> {
+    let id = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get interface name"),
+    };
+    let number_path = path.join("bInterfaceNumber");
+    let class_path = path.join("bInterfaceClass");
+    let subclass_path = path.join("bInterfaceSubClass");
+    let protocol_path = path.join("bInterfaceProtocol");
+    let driver_path = path.join("driver");
+    let supports_autosuspend_path = path.join("supports_autosuspend");
 
-                    // heuristic: don't set Application Specific Interface if there is a choice
-                    if new_class == 0xfe && class != 0 {
-                        continue
-                    }
-                    // heuristic: don't remplace by an empty subclass if possible
-                    if class == new_class && new_subclass == 0 {
-                        continue
-                    }
-                    // heuristic: don't remplace by an empty interface if possible
-                    if class == new_class && subclass == new_subclass && new_protocol == 0 {
-                        continue
-                    }
+    let mut interface = UsbInterface::from(id);
 
-                    class = new_class;
-                    subclass = new_subclass;
+    if let Ok(number_str) = fs::read_to_string(&number_path) {
+        interface.number = u8::from_str_radix(number_str.trim(), 16)?;
+    }
 
-                    protocol = new_protocol;
-                }
-            });
-        });
+    if let Ok(class_str) = fs::read_to_string(&class_path) {
+        if let Ok(class_id) = u16::from_str_radix(class_str.trim(), 16) {
+            interface.kind.class = class_id;
+        }
+    }
+
+    if let Ok(subclass_str) = fs::read_to_string(&subclass_path) {
+        if let Ok(subclass_id) = u16::from_str_radix(subclass_str.trim(), 16) {
+            interface.kind.subclass = subclass_id;
+        }
+    }
+
+    if let Ok(protocol_str) = fs::read_to_string(&protocol_path) {
+        if let Ok(protocol_id) = u16::from_str_radix(protocol_str.trim(), 16) {
+            interface.kind.interface = protocol_id;
+        }
+    }
+
+    if let Some(db) = usb_db {
+        interface.kind.fill_names(db);
+    }
+
+    if let Ok(driver) = fs::read_link(&driver_path) {
+        interface.driver = driver
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+    }
+
+    if let Ok(supports) = fs::read_to_string(&supports_autosuspend_path) {
+        interface.supports_autosuspend = Some(supports.trim() == "1");
+    }
+
+    Ok(interface)
+}
+
+/// Picks the interface that best describes what a device is.
+fn classify_interfaces(interfaces: &[UsbInterface]) -> (u16, u16, u16) {
+    let mut class = 0;
+    let mut subclass = 0;
+    let mut protocol = 0;
+
+    for interface in interfaces {
+        let new_class = interface.kind.class;
+        let new_subclass = interface.kind.subclass;
+        let new_protocol = interface.kind.interface;
+
+        // heuristic: when the current interface is a Mouse, don't override with a Keyboard
+        if class == 0x03 && protocol == 0x02 && new_class == 0x03 && new_protocol == 0x01 {
+            continue;
+        }
+
+        // heuristic: don't set Application Specific Interface if there is a choice
+        if new_class == 0xfe && class != 0 {
+            continue;
+        }
+        // heuristic: don't remplace by an empty subclass if possible
+        if class == new_class && new_subclass == 0 {
+            continue;
+        }
+        // heuristic: don't remplace by an empty interface if possible
+        if class == new_class && subclass == new_subclass && new_protocol == 0 {
+            continue;
+        }
+
+        class = new_class;
+        subclass = new_subclass;
+
+        protocol = new_protocol;
     }
 
-    Ok((class, subclass, protocol))
+    (class, subclass, protocol)
 }
 
-fn make_device(entry: std::fs::DirEntry, usb_db: Option<&Db>) -> Result<UsbDevice> {
-    let m = entry.metadata()?;
-    let rdev = m.st_rdev().to_ne_bytes();
-    let major = rdev[1];
-    let minor = rdev[0] as u16;
-    let minor = minor + ((rdev[2] as u16) << 4);
-
-    let char_path = std::path::Path::new("/sys/dev/char/").join(format!("{}:{}", major, minor));
-    let vendor_path = char_path.join("idVendor");
-    let product_path = char_path.join("idProduct");
-    let product_name_path = char_path.join("product");
-    let class_path = char_path.join("bDeviceClass");
-    let subclass_path = char_path.join("bDeviceSubClass");
-    let protocol_path = char_path.join("bDeviceProtocol");
-    let control = char_path.join("power/control");
-    let wakeup = char_path.join("power/wakeup");
-    let autosuspend_delay = char_path.join("power/autosuspend_delay_ms");
-    let id = major as u32 | ((minor as u32) << 16);
-    let mut usb_device = UsbDevice::from(&char_path, id);
+fn make_device(
+    path: PathBuf,
+    uevent: &HashMap<String, String>,
+    usb_db: Option<&Db>,
+) -> Result<UsbDevice> {
+    let id = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get device name"),
+    };
+    let vendor_path = path.join("idVendor");
+    let product_path = path.join("idProduct");
+    let product_name_path = path.join("product");
+    let serial_path = path.join("serial");
+    let busnum_path = path.join("busnum");
+    let devpath_path = path.join("devpath");
+    let devnum_path = path.join("devnum");
+    let speed_path = path.join("speed");
+    let max_power_path = path.join("bMaxPower");
+    let removable_path = path.join("removable");
+    let version_path = path.join("version");
+    let authorized_path = path.join("authorized");
+    let persist_path = path.join("power/persist");
+    let usb2_lpm_path = path.join("power/usb2_hardware_lpm");
+    let usb3_lpm_u1_path = path.join("power/usb3_hardware_lpm_u1");
+    let usb3_lpm_u2_path = path.join("power/usb3_hardware_lpm_u2");
+    let authorized_default_path = path.join("authorized_default");
+    let class_path = path.join("bDeviceClass");
+    let subclass_path = path.join("bDeviceSubClass");
+    let protocol_path = path.join("bDeviceProtocol");
+    let control = path.join("power/control");
+    let wakeup = path.join("power/wakeup");
+    let autosuspend_delay = path.join("power/autosuspend_delay_ms");
+    let mut usb_device = UsbDevice::from(path, id);
+    usb_device.char_device_path = uevent
+        .get("DEVNAME")
+        .map(|name| Path::new("/dev").join(name));
 
     if let Ok(vendor) = fs::read_to_string(&vendor_path) {
         let vendor_id = u16::from_str_radix(&vendor.trim(), 16)?;
         usb_device.vendor_id = Some(vendor_id);
+        if let Ok(product) = fs::read_to_string(&product_path) {
+            usb_device.product_id = Some(u16::from_str_radix(product.trim(), 16)?);
+        }
         if let Some(vendor) = usb_db.and_then(|db| db.vendors.get(&vendor_id)) {
             usb_device.db_vendor_name = Some(vendor.name.trim().to_string());
 
-            if let Ok(product) = fs::read_to_string(&product_path) {
-                let product_id = u16::from_str_radix(&product.trim(), 16)?;
-                usb_device.product_id = Some(product_id);
-                if let Some(product) = vendor.devices.get(&product_id) {
-                    usb_device.db_product_name = Some(product.trim().to_string());
-                }
+            if let Some(product) = usb_device
+                .product_id
+                .and_then(|product_id| vendor.devices.get(&product_id))
+            {
+                usb_device.db_product_name = Some(product.trim().to_string());
             }
         }
     }
 
+    usb_device.identity = UsbIdentity {
+        busnum: fs::read_to_string(&busnum_path)?.trim().parse()?,
+        devpath: fs::read_to_string(&devpath_path)?.trim().to_owned(),
+        vendor_id: usb_device.vendor_id.unwrap_or_default(),
+        product_id: usb_device.product_id.unwrap_or_default(),
+        serial: fs::read_to_string(&serial_path)
+            .ok()
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty()),
+    };
+
     if let Ok(product_name) = fs::read_to_string(&product_name_path) {
         usb_device.product_name = Some(product_name.trim().to_owned());
     }
 
+    let read_attribute = |path: &Path| {
+        fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+    };
+    usb_device.devnum = read_attribute(&devnum_path).and_then(|s| s.parse().ok());
+    usb_device.speed = read_attribute(&speed_path);
+    usb_device.max_power = read_attribute(&max_power_path);
+    usb_device.removable = read_attribute(&removable_path);
+    usb_device.version = read_attribute(&version_path);
+
+    match list_interfaces(&usb_device.device_path, usb_db) {
+        Ok(interfaces) => usb_device.interfaces = interfaces,
+        Err(e) => warn!("ignoring error listing interfaces: {}", e),
+    }
+
     if let Ok(class_str) = fs::read_to_string(&class_path) {
         if let Ok(class_id) = u16::from_str_radix(&class_str.trim(), 16) {
             if class_id == 0x00 {
                 // we have to look into an interface to have an idea what this is
-                if let Ok((class, subclass, protocol)) = interface_info(&char_path.canonicalize()?)
-                {
-                    usb_device.kind.class = class;
-                    usb_device.kind.subclass = subclass;
-                    usb_device.kind.interface = protocol;
-                }
+                let (class, subclass, protocol) = classify_interfaces(&usb_device.interfaces);
+                usb_device.kind.class = class;
+                usb_device.kind.subclass = subclass;
+                usb_device.kind.interface = protocol;
             } else {
                 usb_device.kind.class = class_id;
                 if let Ok(subclass_str) = fs::read_to_string(&subclass_path) {
@@ -463,24 +1015,15 @@ fn make_device(entry: std::fs::DirEntry, usb_db: Option<&Db>) -> Result<UsbDevic
                     && usb_device.kind.interface == 0x01
                 {
                     // we have to look into an interface to have an idea what this is
-                    if let Ok((class, subclass, protocol)) =
-                        interface_info(&char_path.canonicalize()?)
-                    {
-                        usb_device.kind.class = class;
-                        usb_device.kind.subclass = subclass;
-                        usb_device.kind.interface = protocol;
-                    }
+                    let (class, subclass, protocol) = classify_interfaces(&usb_device.interfaces);
+                    usb_device.kind.class = class;
+                    usb_device.kind.subclass = subclass;
+                    usb_device.kind.interface = protocol;
                 }
             }
 
-            if let Some(c) = usb_db.and_then(|db| db.classes.get(&usb_device.kind.class)) {
-                usb_device.kind.class_name = Some(c.name.clone());
-                if let Some(sub) = c.subclasses.get(&usb_device.kind.subclass) {
-                    usb_device.kind.subclass_name = Some(sub.name.trim().to_string());
-                    if let Some(int) = sub.subclasses.get(&usb_device.kind.interface) {
-                        usb_device.kind.interface_name = Some(int.name.trim().to_string());
-                    }
-                }
+            if let Some(db) = usb_db {
+                usb_device.kind.fill_names(db);
             }
         }
     }
@@ -492,13 +1035,19 @@ fn make_device(entry: std::fs::DirEntry, usb_db: Option<&Db>) -> Result<UsbDevic
     };
     usb_device.autosuspend = autosuspend;
 
-    match fs::read_to_string(&autosuspend_delay)?
+    let read_delay = match fs::read_to_string(&autosuspend_delay)?
         .trim()
         .parse::<i64>()?
     {
-        -1 => usb_device.autosuspend = false,
-        i => usb_device.delay = i as u64,
-    }
+        -1 => {
+            usb_device.autosuspend = false;
+            None
+        }
+        i => {
+            usb_device.delay = i as u64;
+            Some(usb_device.delay)
+        }
+    };
 
     let wakeup = match fs::read_to_string(&wakeup) {
         Ok(s) => match s.trim() {
@@ -506,11 +1055,40 @@ fn make_device(entry: std::fs::DirEntry, usb_db: Option<&Db>) -> Result<UsbDevic
             "disabled" => Some(false),
             _ => None,
         },
-        Err(_) => {
-            None
-        }
+        Err(_) => None,
     };
     usb_device.allow_wakeup = wakeup;
+    usb_device.read_power = (usb_device.autosuspend, read_delay, wakeup);
+
+    // those only exist when the device (and its hub) support LPM
+    let read_lpm = |path: &Path| fs::read_to_string(path).ok().map(|s| s.trim() == "enabled");
+    // the kernel makes it read-only when the port can't change it
+    let writable = |path: &Path| {
+        fs::metadata(path)
+            .map(|m| m.permissions().mode() & 0o200 != 0)
+            .unwrap_or(false)
+    };
+    if writable(&usb2_lpm_path) {
+        usb_device.usb2_hardware_lpm = read_lpm(&usb2_lpm_path);
+        usb_device.read_usb2_hardware_lpm = usb_device.usb2_hardware_lpm;
+    }
+    usb_device.usb3_hardware_lpm_u1 = read_lpm(&usb3_lpm_u1_path);
+    usb_device.usb3_hardware_lpm_u2 = read_lpm(&usb3_lpm_u2_path);
+
+    if let Ok(persist) = fs::read_to_string(&persist_path) {
+        usb_device.persist = Some(persist.trim() == "1");
+    }
+    usb_device.read_persist = usb_device.persist;
+
+    if let Ok(authorized) = fs::read_to_string(&authorized_path) {
+        usb_device.authorized = Some(authorized.trim() != "0");
+    }
+    if let Ok(authorized_default) = fs::read_to_string(&authorized_default_path) {
+        usb_device.authorized_default = authorized_default.parse().ok();
+    }
+    // only what changed is written: deauthorizing is destructive
+    usb_device.read_authorized = usb_device.authorized;
+    usb_device.read_authorized_default = usb_device.authorized_default;
 
     Ok(usb_device)
 }
@@ -0,0 +1,237 @@
+use crate::fs::write_string_privileged;
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::Path;
+
+const ACPI_WAKEUP_PATH: &str = "/proc/acpi/wakeup";
+
+/// An entry of the ACPI wakeup table, an ACPI device the firmware lets wake
+/// the system up.
+#[derive(Clone, Debug)]
+pub struct AcpiWakeupDevice {
+    name: String,
+    sleep_state: String,
+    enabled: bool,
+    nodes: Vec<AcpiNode>,
+}
+
+/// A device in sysfs an ACPI device is bound to, like `pci:0000:00:14.0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AcpiNode {
+    pub bus: String,
+    pub id: String,
+}
+
+impl AcpiWakeupDevice {
+    /// The name of the device in the ACPI tables (`XHC`, `LID0`...).
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The deepest sleep state the device can wake the system up from (`S3`, `S4`...).
+    pub fn sleep_state(&self) -> &str {
+        &self.sleep_state
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// The devices in sysfs this entry is bound to, if any.
+    pub fn nodes(&self) -> &[AcpiNode] {
+        &self.nodes
+    }
+
+    /// The settings of this entry, as stored in a [`Profile`](crate::profile::Profile).
+    pub fn profile_settings(&self) -> Vec<(String, String)> {
+        let wakeup = if self.enabled { "enabled" } else { "disabled" };
+
+        vec![("wakeup".to_owned(), wakeup.to_owned())]
+    }
+
+    /// Changes one setting from a [`Profile`](crate::profile::Profile).
+    pub fn apply_profile_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "wakeup" => match value {
+                "enabled" => self.enabled = true,
+                "disabled" => self.enabled = false,
+                v => bail!("invalid value '{}' for '{}'", v, key),
+            },
+            _ => bail!("unknown acpi wakeup setting '{}'", key),
+        }
+
+        Ok(())
+    }
+
+    /// Brings the entry to the wanted state.
+    ///
+    /// Writing the name of a device to the table toggles it, so the table is
+    /// read again before and after writing: nothing is written if the entry
+    /// is already in the right state, and a write that didn't take is an
+    /// error instead of a silent toggle in the wrong direction.
+    pub async fn save(&self) -> Result<()> {
+        if current_state(&self.name)? == self.enabled {
+            return Ok(());
+        }
+
+        trace!("toggling '{}' in {}", self.name, ACPI_WAKEUP_PATH);
+        write_string_privileged(Path::new(ACPI_WAKEUP_PATH), self.name.clone()).await?;
+
+        // the entry and its devices are toggled separately, and it shows as
+        // enabled if any of them is
+        if current_state(&self.name)? != self.enabled {
+            bail!(
+                "{} didn't change, the wakeup of its device probably differs from it",
+                self.name
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn current_state(name: &str) -> Result<bool> {
+    read_acpi_wakeup()?
+        .into_iter()
+        .find(|d| d.name == name)
+        .map(|d| d.enabled)
+        .ok_or_else(|| anyhow!("{} is no longer in {}", name, ACPI_WAKEUP_PATH))
+}
+
+/// Reads the ACPI wakeup table.
+pub fn read_acpi_wakeup() -> Result<Vec<AcpiWakeupDevice>> {
+    let content = fs::read_to_string(ACPI_WAKEUP_PATH)?;
+
+    Ok(parse_acpi_wakeup(&content))
+}
+
+/// Parses `/proc/acpi/wakeup`, which looks like:
+///
+/// ```text
+/// Device  S-state   Status   Sysfs node
+/// GLAN      S4    *enabled   pci:0000:00:1f.6
+/// XHC       S3    *enabled   pci:0000:00:14.0
+/// RP01      S4    *disabled
+/// LID0      S4    *enabled   platform:PNP0C0D:00
+///                            platform:PNP0C0D:01
+/// ```
+///
+/// An entry bound to several devices lists the others on the next lines.
+/// Entries can share a name, in which case writing it toggles all of them:
+/// only the first one is kept.
+fn parse_acpi_wakeup(content: &str) -> Vec<AcpiWakeupDevice> {
+    let mut devices: Vec<AcpiWakeupDevice> = Vec::new();
+    let mut skip_nodes = false;
+
+    for line in content.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            [node] => {
+                if let (Some(device), false) = (devices.last_mut(), skip_nodes) {
+                    device.nodes.extend(parse_node(node));
+                }
+            }
+            [name, sleep_state, status, nodes @ ..] => {
+                skip_nodes = devices.iter().any(|d| d.name == *name);
+                if skip_nodes {
+                    debug!("ignoring duplicate acpi wakeup entry {}", name);
+                    continue;
+                }
+
+                // the star only tells the device can also wake itself up at runtime
+                let enabled = status.trim_start_matches('*') == "enabled";
+                devices.push(AcpiWakeupDevice {
+                    name: (*name).to_owned(),
+                    sleep_state: (*sleep_state).to_owned(),
+                    enabled,
+                    nodes: nodes.iter().filter_map(|n| parse_node(n)).collect(),
+                });
+            }
+            _ => trace!("ignoring acpi wakeup line '{}'", line),
+        }
+    }
+
+    devices
+}
+
+fn parse_node(node: &str) -> Option<AcpiNode> {
+    let separator = node.find(':')?;
+
+    Some(AcpiNode {
+        bus: node[..separator].to_owned(),
+        id: node[separator + 1..].to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "Device\tS-state\t  Status   Sysfs node\n";
+
+    #[test]
+    fn parse_entries() {
+        let content = format!(
+            "{}{}{}",
+            HEADER, "GLAN\t  S4\t*enabled   pci:0000:00:1f.6\n", "RP01\t  S4\t*disabled\n",
+        );
+        let devices = parse_acpi_wakeup(&content);
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].get_name(), "GLAN");
+        assert_eq!(devices[0].sleep_state(), "S4");
+        assert!(devices[0].enabled());
+        assert_eq!(
+            devices[0].nodes(),
+            &[AcpiNode {
+                bus: "pci".to_owned(),
+                id: "0000:00:1f.6".to_owned(),
+            }]
+        );
+        assert_eq!(devices[1].get_name(), "RP01");
+        assert!(!devices[1].enabled());
+        assert!(devices[1].nodes().is_empty());
+    }
+
+    #[test]
+    fn parse_continuation_lines() {
+        let content = format!(
+            "{}{}{}{}",
+            HEADER,
+            "LID0\t  S4\t*enabled   platform:PNP0C0D:00\n",
+            "\t\t\t\t  platform:PNP0C0D:01\n",
+            "XHC\t  S3\t*enabled   pci:0000:00:14.0\n",
+        );
+        let devices = parse_acpi_wakeup(&content);
+
+        assert_eq!(devices.len(), 2);
+        let ids: Vec<&str> = devices[0].nodes().iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["PNP0C0D:00", "PNP0C0D:01"]);
+        assert_eq!(devices[1].nodes().len(), 1);
+    }
+
+    #[test]
+    fn parse_duplicate_names() {
+        let content = format!(
+            "{}{}{}{}{}",
+            HEADER,
+            "PXSX\t  S4\t*disabled  pci:0000:02:00.0\n",
+            "PXSX\t  S4\t*enabled   pci:0000:03:00.0\n",
+            "\t\t\t\t  pci:0000:04:00.0\n",
+            "RP01\t  S4\t*disabled\n",
+        );
+        let devices = parse_acpi_wakeup(&content);
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].get_name(), "PXSX");
+        assert!(!devices[0].enabled());
+        let ids: Vec<&str> = devices[0].nodes().iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["0000:02:00.0"]);
+        assert_eq!(devices[1].get_name(), "RP01");
+    }
+}
@@ -0,0 +1,141 @@
+mod acpi;
+
+pub use acpi::{read_acpi_wakeup, AcpiNode, AcpiWakeupDevice};
+
+use anyhow::*;
+use log::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A wakeup source, with its statistics since boot.
+#[derive(Clone, Debug)]
+pub struct WakeupSource {
+    id: String,
+    name: String,
+    device: Option<WakeupDevice>,
+    event_count: u64,
+    wakeup_count: u64,
+    active_time_ms: u64,
+    last_change_ms: u64,
+}
+
+/// The device a wakeup source belongs to.
+#[derive(Clone, Debug)]
+pub struct WakeupDevice {
+    /// The bus or class of the device (`usb`, `pci`, `acpi`...).
+    pub subsystem: Option<String>,
+    /// The name of the device in sysfs, like the ids of the USB and PCI devices.
+    pub id: String,
+}
+
+impl WakeupSource {
+    /// The name of the wakeup source in sysfs (`wakeup0`...).
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn device(&self) -> Option<&WakeupDevice> {
+        self.device.as_ref()
+    }
+
+    /// How many times the source signaled a wakeup event.
+    pub fn event_count(&self) -> u64 {
+        self.event_count
+    }
+
+    /// How many times the source aborted a suspend or woke the system up.
+    pub fn wakeup_count(&self) -> u64 {
+        self.wakeup_count
+    }
+
+    /// How long the source kept the system awake, in total.
+    pub fn active_time(&self) -> Duration {
+        Duration::from_millis(self.active_time_ms)
+    }
+
+    /// How long ago the source was last active, if it ever was.
+    pub fn last_change_ago(&self) -> Option<Duration> {
+        if self.last_change_ms == 0 {
+            return None;
+        }
+
+        // last_change_ms is on the monotonic clock, which stops during
+        // suspend unlike the uptime
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 {
+            return None;
+        }
+        let now_ms = now.tv_sec as u64 * 1000 + now.tv_nsec as u64 / 1_000_000;
+
+        Some(Duration::from_millis(
+            now_ms.saturating_sub(self.last_change_ms),
+        ))
+    }
+}
+
+/// Lists the wakeup sources, the ones that woke the system up the most first.
+pub fn list_sources() -> Result<Vec<WakeupSource>> {
+    debug!("listing wakeup sources");
+
+    let mut sources = Vec::new();
+
+    let dir = PathBuf::from("/sys/class/wakeup/");
+    for entry in fs::read_dir(&dir)? {
+        match_warn!(entry, "ignoring error while enumerating wakeup sources: {}", entry => {
+            let source = make_source(&entry.path());
+            match_warn!(source, "ignoring error reading wakeup source: {}", source => {
+                sources.push(source);
+            });
+        });
+    }
+
+    sources.sort_by(|a, b| {
+        b.wakeup_count
+            .cmp(&a.wakeup_count)
+            .then(b.event_count.cmp(&a.event_count))
+            .then(a.name.cmp(&b.name))
+    });
+
+    Ok(sources)
+}
+
+fn make_source(path: &Path) -> Result<WakeupSource> {
+    let id: String = match path.file_name() {
+        Some(id) => id.to_string_lossy().into(),
+        None => bail!("unable to get wakeup source name"),
+    };
+    let read =
+        |name: &str| -> Result<u64> { Ok(fs::read_to_string(path.join(name))?.trim().parse()?) };
+
+    let device = path.join("device").canonicalize().ok().and_then(|device| {
+        let subsystem = device
+            .join("subsystem")
+            .canonicalize()
+            .ok()
+            .and_then(|s| s.file_name().map(|s| s.to_string_lossy().into_owned()));
+        device.file_name().map(|id| WakeupDevice {
+            subsystem,
+            id: id.to_string_lossy().into_owned(),
+        })
+    });
+
+    Ok(WakeupSource {
+        name: fs::read_to_string(path.join("name"))
+            .map(|n| n.trim().to_owned())
+            .unwrap_or_else(|_| id.clone()),
+        id,
+        device,
+        event_count: read("event_count")?,
+        wakeup_count: read("wakeup_count")?,
+        active_time_ms: read("active_time_ms")?,
+        last_change_ms: read("last_change_ms")?,
+    })
+}